@@ -0,0 +1,266 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, Ident, Lit, LitStr, Type};
+
+// Derives the conversions between a struct with named fields and a `Hash`:
+// - `From<T> for Hash` and `From<T> for HashValue`, so `T` can be a node of
+//   another derived struct
+// - `TryFrom<&Hash> for T` and `TryFrom<&HashValue> for T`, fields have to
+//   match their type exactly
+// - `T::karabo_schema()`, the Karabo schema of the struct
+//
+// Fields take `#[karabo(...)]` options:
+// - `key = "path"`, the key of the node, the field name by default
+// - `attr(unit = "m", ...)`, attributes of the node
+// - `default = expr`, used when the node is missing
+// - `coerce`, the field is read with `HashValue::coerce`, e.g. an `f64`
+//   field from any number
+// - `displayed_name = "..."`, `description = "..."` for the schema
+// - `access = "init" | "read_only" | "reconfigurable"`, the schema
+//   `accessMode`, reconfigurable by default
+#[proc_macro_derive(KaraboHash, attributes(karabo))]
+pub fn derive_karabo_hash(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct Field {
+    ident: Ident,
+    ty: Type,
+    key: String,
+    attrs: Vec<(String, Lit)>,
+    default: Option<Expr>,
+    coerce: bool,
+    displayed_name: Option<LitStr>,
+    description: Option<LitStr>,
+    access: i32,
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<Field> {
+    let ident = field.ident.clone().expect("named field");
+    let mut parsed = Field {
+        key: ident.to_string(),
+        ident,
+        ty: field.ty.clone(),
+        attrs: Vec::new(),
+        default: None,
+        coerce: false,
+        displayed_name: None,
+        description: None,
+        access: 4,
+    };
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("karabo"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("key") {
+                parsed.key = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("attr") {
+                meta.parse_nested_meta(|inner| {
+                    let name = match inner.path.get_ident() {
+                        Some(name) => name.to_string(),
+                        None => return Err(inner.error("expected an attribute name")),
+                    };
+                    parsed.attrs.push((name, inner.value()?.parse()?));
+                    Ok(())
+                })?;
+            } else if meta.path.is_ident("default") {
+                parsed.default = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("coerce") {
+                parsed.coerce = true;
+            } else if meta.path.is_ident("displayed_name") {
+                parsed.displayed_name = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("description") {
+                parsed.description = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("access") {
+                let access: LitStr = meta.value()?.parse()?;
+                parsed.access = match access.value().as_str() {
+                    "init" => 1,
+                    "read_only" => 2,
+                    "reconfigurable" => 4,
+                    _ => {
+                        return Err(syn::Error::new(
+                            access.span(),
+                            "expected \"init\", \"read_only\" or \"reconfigurable\"",
+                        ))
+                    }
+                };
+            } else {
+                return Err(meta.error("unknown karabo option"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(parsed)
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    input,
+                    "KaraboHash needs a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "KaraboHash can only be derived for structs",
+            ))
+        }
+    };
+    let fields = fields
+        .iter()
+        .map(parse_field)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "KaraboHash can't be derived for generic structs",
+        ));
+    }
+
+    let name = &input.ident;
+    let class_id = name.to_string();
+
+    let inserts = fields.iter().map(|field| {
+        let Field { ident, key, .. } = field;
+        let attrs = field.attrs.iter().map(|(attr, value)| {
+            quote! { attrs.insert(#attr, ::karabo_wasm::types::HashValue::from(#value)); }
+        });
+        quote! {
+            let mut attrs = ::karabo_wasm::attributes::Attributes::new();
+            #(#attrs)*
+            if !hash.insert_attrs(#key, ::karabo_wasm::types::HashValue::from(value.#ident), attrs) {
+                ::core::panic!("invalid key {}", #key);
+            }
+        }
+    });
+
+    let reads = fields.iter().map(|field| {
+        let Field { ident, ty, key, .. } = field;
+        let read = match field.coerce {
+            true => quote! { value.coerce::<#ty>()? },
+            false => quote! {
+                <#ty as ::core::convert::TryFrom<&::karabo_wasm::types::HashValue>>::try_from(value)?
+            },
+        };
+        let missing = match &field.default {
+            Some(default) => quote! { ::core::convert::Into::into(#default) },
+            None => quote! {
+                return ::core::result::Result::Err(::karabo_wasm::types::TypeError::MissingPath {
+                    path: ::std::string::String::from(#key),
+                })
+            },
+        };
+        quote! {
+            #ident: match hash.get(#key) {
+                ::core::option::Option::Some(value) => #read,
+                ::core::option::Option::None => #missing,
+            },
+        }
+    });
+
+    let properties = fields.iter().map(|field| {
+        let Field { ty, key, access, .. } = field;
+        let displayed_name = match &field.displayed_name {
+            Some(displayed_name) => quote! { #displayed_name },
+            None => quote! { #key },
+        };
+        let description = field.description.iter().map(|description| {
+            quote! { attrs.insert("description", ::karabo_wasm::types::HashValue::from(#description)); }
+        });
+        let default = field.default.iter().map(|default| {
+            quote! {
+                let default: #ty = ::core::convert::Into::into(#default);
+                attrs.insert("defaultValue", ::karabo_wasm::types::HashValue::from(default));
+            }
+        });
+        let attrs = field.attrs.iter().map(|(attr, value)| {
+            quote! { attrs.insert(#attr, ::karabo_wasm::types::HashValue::from(#value)); }
+        });
+        quote! {
+            let mut attrs = ::karabo_wasm::attributes::Attributes::new();
+            attrs.insert("displayedName", ::karabo_wasm::types::HashValue::from(#displayed_name));
+            attrs.insert("accessMode", ::karabo_wasm::types::HashValue::Int32(#access));
+            #(#description)*
+            #(#default)*
+            #(#attrs)*
+            ::karabo_wasm::schema::add_schema_property::<#ty>(&mut hash, #key, attrs);
+        }
+    });
+
+    Ok(quote! {
+        impl ::core::convert::From<#name> for ::karabo_wasm::hash::Hash {
+            fn from(value: #name) -> Self {
+                let mut hash = ::karabo_wasm::hash::Hash::new();
+                #(#inserts)*
+                hash
+            }
+        }
+
+        impl ::core::convert::From<#name> for ::karabo_wasm::types::HashValue {
+            fn from(value: #name) -> Self {
+                ::karabo_wasm::types::HashValue::Hash(::karabo_wasm::hash::Hash::from(value))
+            }
+        }
+
+        impl ::core::convert::TryFrom<&::karabo_wasm::hash::Hash> for #name {
+            type Error = ::karabo_wasm::types::TypeError;
+
+            fn try_from(hash: &::karabo_wasm::hash::Hash) -> ::core::result::Result<Self, Self::Error> {
+                ::core::result::Result::Ok(#name {
+                    #(#reads)*
+                })
+            }
+        }
+
+        impl ::core::convert::TryFrom<&::karabo_wasm::types::HashValue> for #name {
+            type Error = ::karabo_wasm::types::TypeError;
+
+            fn try_from(
+                value: &::karabo_wasm::types::HashValue,
+            ) -> ::core::result::Result<Self, Self::Error> {
+                match value {
+                    ::karabo_wasm::types::HashValue::Hash(hash) => Self::try_from(hash),
+                    value => ::core::result::Result::Err(::karabo_wasm::types::TypeError::Mismatch {
+                        expected: "HASH",
+                        found: ::karabo_wasm::types::get_typename(value),
+                    }),
+                }
+            }
+        }
+
+        impl<'a> ::karabo_wasm::convert::Coerce<'a> for #name {
+            fn coerce(
+                value: &'a ::karabo_wasm::types::HashValue,
+            ) -> ::core::result::Result<Self, ::karabo_wasm::types::TypeError> {
+                Self::try_from(value)
+            }
+        }
+
+        impl ::karabo_wasm::schema::SchemaType for #name {
+            fn node_schema() -> ::core::option::Option<::karabo_wasm::hash::Hash> {
+                ::core::option::Option::Some(Self::karabo_schema().hash)
+            }
+        }
+
+        impl #name {
+            pub fn karabo_schema() -> ::karabo_wasm::schema::Schema {
+                let mut hash = ::karabo_wasm::hash::Hash::new();
+                #(#properties)*
+                ::karabo_wasm::schema::Schema::new(#class_id.to_string(), hash)
+            }
+        }
+    })
+}
@@ -2,7 +2,8 @@ use std::fmt;
 
 use std::collections::HashMap;
 
-use crate::types::{get_hashtype, HashValue};
+use crate::hash::values_equal;
+use crate::types::HashValue;
 
 #[derive(Clone, Debug)]
 pub struct Attribute {
@@ -35,11 +36,10 @@ impl Attributes {
         if self.store.is_empty() {
             return Vec::new();
         }
-        return self
-            .store
+        self.store
             .iter()
             .map(|x| x.key.clone())
-            .collect::<Vec<String>>();
+            .collect::<Vec<String>>()
     }
 
     pub fn get_index(&self, index: usize) -> Option<&Attribute> {
@@ -50,6 +50,22 @@ impl Attributes {
         self.store.len()
     }
 
+    pub fn iter(&self) -> AttributesIterator<'_> {
+        AttributesIterator {
+            attrs: self.store.iter(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> AttributesIteratorMut<'_> {
+        AttributesIteratorMut {
+            attrs: self.store.iter_mut(),
+        }
+    }
+
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &HashValue> {
+        self.store.iter().map(|attr| &attr.value)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.store.is_empty()
     }
@@ -76,6 +92,53 @@ impl Attributes {
         }
     }
 
+    // Same keys in the same order with the same values and types
+    pub fn fully_equals(&self, other: &Attributes) -> bool {
+        self.len() == other.len()
+            && self
+                .store
+                .iter()
+                .zip(&other.store)
+                .all(|(a, b)| a.key == b.key && values_equal(&a.value, &b.value, true, true))
+    }
+
+    // Same keys with the same values and types, in any order
+    pub fn equals_ignoring_order(&self, other: &Attributes) -> bool {
+        self.len() == other.len()
+            && self
+                .store
+                .iter()
+                .all(|attr| other.get(&attr.key) == Some(&attr.value))
+    }
+
+    // Removes the attribute `key`, the order of the remaining ones is kept
+    pub fn remove(&mut self, key: &str) -> Option<Attribute> {
+        let idx = self.key_map.remove(key)?;
+        let attr = self.store.remove(idx);
+        self.reindex(idx);
+        Some(attr)
+    }
+
+    pub fn retain<F: FnMut(&Attribute) -> bool>(&mut self, f: F) {
+        self.store.retain(f);
+        self.reindex(0);
+    }
+
+    pub fn clear(&mut self) {
+        self.store.clear();
+        self.key_map.clear();
+    }
+
+    // rebuilds the positions of the attributes from `start` on
+    fn reindex(&mut self, start: usize) {
+        if start == 0 {
+            self.key_map.clear();
+        }
+        for (idx, attr) in self.store.iter().enumerate().skip(start) {
+            self.key_map.insert(attr.key.clone(), idx);
+        }
+    }
+
     #[inline]
     pub fn insert(&mut self, key: &str, value: HashValue) {
         self.insert_index(key, value);
@@ -97,50 +160,78 @@ impl Attributes {
     }
 }
 
+// Borrowing iterator over the attributes, in insertion order
 pub struct AttributesIterator<'a> {
-    attrs: &'a Attributes,
-    index: usize,
+    attrs: std::slice::Iter<'a, Attribute>,
 }
 
 impl<'a> Iterator for AttributesIterator<'a> {
-    type Item = Attribute;
+    type Item = (&'a str, &'a HashValue);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let result = self.attrs.get_index(self.index);
-        self.index += 1;
-        match result {
-            Some(x) => {
-                self.index += 1;
-                Some(x.clone())
-            }
-            None => None,
-        }
+        self.attrs
+            .next()
+            .map(|attr| (attr.key.as_str(), &attr.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.attrs.size_hint()
+    }
+}
+
+impl ExactSizeIterator for AttributesIterator<'_> {}
+
+pub struct AttributesIteratorMut<'a> {
+    attrs: std::slice::IterMut<'a, Attribute>,
+}
+
+impl<'a> Iterator for AttributesIteratorMut<'a> {
+    type Item = (&'a str, &'a mut HashValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.attrs
+            .next()
+            .map(|attr| (attr.key.as_str(), &mut attr.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.attrs.size_hint()
     }
 }
 
+impl ExactSizeIterator for AttributesIteratorMut<'_> {}
+
+impl<'a> IntoIterator for &'a Attributes {
+    type Item = (&'a str, &'a HashValue);
+    type IntoIter = AttributesIterator<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Attributes {
+    type Item = (&'a str, &'a mut HashValue);
+    type IntoIter = AttributesIteratorMut<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl IntoIterator for Attributes {
+    type Item = Attribute;
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.store.into_iter()
+    }
+}
+
+// Same as `equals_ignoring_order`
 impl PartialEq for Attributes {
     fn eq(&self, other: &Self) -> bool {
-        if self.len() != other.len() {
-            return false;
-        }
-        if self
-            .keys()
-            .iter()
-            .filter(|&a| {
-                other.get(a).is_some()
-                    && get_hashtype(self.get(a).expect("")) != get_hashtype(other.get(a).expect(""))
-            })
-            .count()
-            != 0
-        {
-            return false;
-        }
-        for key in self.keys() {
-            if self.get(&key) != other.get(&key) {
-                return false;
-            }
-        }
-        true
+        self.equals_ignoring_order(other)
     }
 }
 
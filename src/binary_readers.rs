@@ -1,348 +1,741 @@
-use std::io::{Read, Result};
+use std::fmt;
+use std::io::{ErrorKind, Read};
 
 use crate::attributes::Attributes;
+use crate::bulk::{as_bytes_mut, Plain};
 use crate::hash::Hash;
 use crate::schema::Schema;
-use crate::types::HashValue;
+use crate::types::{Complex, HashValue};
+
+// Every variant carries the byte offset at which decoding failed and the
+// dotted path of the key that was being decoded at the time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    Truncated {
+        offset: usize,
+        path: String,
+    },
+    UnknownType {
+        type_: u32,
+        offset: usize,
+        path: String,
+    },
+    InvalidUtf8 {
+        offset: usize,
+        path: String,
+    },
+    OversizedLength {
+        length: u64,
+        offset: usize,
+        path: String,
+    },
+    Io {
+        kind: ErrorKind,
+        offset: usize,
+        path: String,
+    },
+    TooDeep {
+        depth: usize,
+        offset: usize,
+        path: String,
+    },
+    // the content does not match the announced length of a frame
+    LengthMismatch {
+        length: u64,
+        offset: usize,
+        path: String,
+    },
+}
 
-fn read_vbool<R: Read>(buf: &mut R) -> Result<Vec<bool>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let mut arr = vec![0u8; size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    let ret = arr.iter().map(|&e| e != 0_u8).collect();
-    Ok(ret)
+// Limits applied while decoding, lengths are checked before anything is allocated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeOptions {
+    // nesting of hashes, vectors of hashes and schemas, the top level hash is 1
+    pub max_depth: usize,
+    // number of elements in a vector, including vectors of strings and hashes
+    pub max_vector_len: usize,
+    // number of bytes in a single string
+    pub max_string_len: usize,
+    // number of bytes consumed from the input
+    pub max_total_bytes: usize,
 }
 
-fn read_vchar<R: Read>(buf: &mut R) -> Result<Vec<char>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let mut arr = vec![0u8; size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    let ret = arr.iter().map(|&e| e as char).collect();
-    Ok(ret)
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions {
+            max_depth: 64,
+            max_vector_len: 1 << 28,
+            max_string_len: 1 << 24,
+            max_total_bytes: 1 << 30,
+        }
+    }
 }
 
-fn read_vu8<R: Read>(buf: &mut R) -> Result<Vec<u8>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let mut arr = vec![0u8; size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    Ok(arr)
+impl DecodeError {
+    pub fn offset(&self) -> usize {
+        match self {
+            DecodeError::Truncated { offset, .. }
+            | DecodeError::UnknownType { offset, .. }
+            | DecodeError::InvalidUtf8 { offset, .. }
+            | DecodeError::OversizedLength { offset, .. }
+            | DecodeError::Io { offset, .. }
+            | DecodeError::TooDeep { offset, .. }
+            | DecodeError::LengthMismatch { offset, .. } => *offset,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
+            DecodeError::Truncated { path, .. }
+            | DecodeError::UnknownType { path, .. }
+            | DecodeError::InvalidUtf8 { path, .. }
+            | DecodeError::OversizedLength { path, .. }
+            | DecodeError::Io { path, .. }
+            | DecodeError::TooDeep { path, .. }
+            | DecodeError::LengthMismatch { path, .. } => path,
+        }
+    }
 }
 
-fn read_vi8<R: Read>(buf: &mut R) -> Result<Vec<i8>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let mut arr = vec![0u8; size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    let ret = arr.iter().map(|&e| e as i8).collect();
-    Ok(ret)
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Truncated { .. } => write!(f, "input truncated"),
+            DecodeError::UnknownType { type_, .. } => write!(f, "unknown type {type_}"),
+            DecodeError::InvalidUtf8 { .. } => write!(f, "invalid utf-8 sequence"),
+            DecodeError::OversizedLength { length, .. } => {
+                write!(f, "length {length} is too large")
+            }
+            DecodeError::Io { kind, .. } => write!(f, "io error {kind}"),
+            DecodeError::TooDeep { depth, .. } => write!(f, "nesting deeper than {depth}"),
+            DecodeError::LengthMismatch { length, .. } => {
+                write!(f, "content does not match the length {length}")
+            }
+        }?;
+        write!(f, " at offset {} (key '{}')", self.offset(), self.path())
+    }
 }
 
-fn read_vi16<R: Read>(buf: &mut R) -> Result<Vec<i16>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let chunk_size: usize = 2;
-    let mut arr = vec![0u8; size * chunk_size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    let ret = arr
-        .chunks(chunk_size)
-        .map(|chunk| {
-            let sized: [u8; 2] = chunk.try_into().expect("slice with incorrect length");
-            i16::from_le_bytes(sized)
-        })
-        .collect();
-    Ok(ret)
+impl std::error::Error for DecodeError {}
+
+// Wraps the reader to keep track of the position and the key being decoded
+pub(crate) struct Decoder<R> {
+    buf: R,
+    pub(crate) offset: usize,
+    pub(crate) path: String,
+    depth: usize,
+    pub(crate) opts: DecodeOptions,
 }
 
-fn read_vu16<R: Read>(buf: &mut R) -> Result<Vec<u16>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let chunk_size: usize = 2;
-    let mut arr = vec![0u8; size * chunk_size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    let ret = arr
-        .chunks(chunk_size)
-        .map(|chunk| {
-            let sized: [u8; 2] = chunk.try_into().expect("slice with incorrect length");
-            u16::from_le_bytes(sized)
-        })
-        .collect();
-    Ok(ret)
+impl<R: Read> Decoder<R> {
+    pub(crate) fn new(buf: R, opts: &DecodeOptions) -> Self {
+        Decoder {
+            buf,
+            offset: 0,
+            path: String::new(),
+            depth: 0,
+            opts: *opts,
+        }
+    }
+
+    fn read_exact(&mut self, out: &mut [u8]) -> Result<(), DecodeError> {
+        self.check_total(out.len(), self.offset)?;
+        match self.buf.read_exact(out) {
+            Ok(()) => {
+                self.offset += out.len();
+                Ok(())
+            }
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => Err(DecodeError::Truncated {
+                offset: self.offset,
+                path: self.path.clone(),
+            }),
+            Err(err) => Err(DecodeError::Io {
+                kind: err.kind(),
+                offset: self.offset,
+                path: self.path.clone(),
+            }),
+        }
+    }
+
+    // discards `size` bytes without buffering them
+    fn skip(&mut self, size: usize) -> Result<(), DecodeError> {
+        let offset = self.offset;
+        self.check_total(size, offset)?;
+        let mut limited = (&mut self.buf).take(size as u64);
+        match std::io::copy(&mut limited, &mut std::io::sink()) {
+            Ok(copied) if copied == size as u64 => {
+                self.offset += size;
+                Ok(())
+            }
+            Ok(copied) => Err(DecodeError::Truncated {
+                offset: offset + copied as usize,
+                path: self.path.clone(),
+            }),
+            Err(err) => Err(DecodeError::Io {
+                kind: err.kind(),
+                offset,
+                path: self.path.clone(),
+            }),
+        }
+    }
+
+    // appends a key to the current path, returns the mark to restore it
+    pub(crate) fn push_path(&mut self, key: &str) -> usize {
+        let mark = self.path.len();
+        if !self.path.is_empty() {
+            self.path.push('.');
+        }
+        self.path.push_str(key);
+        mark
+    }
+
+    pub(crate) fn push_path_suffix(&mut self, suffix: &str) -> usize {
+        let mark = self.path.len();
+        self.path.push_str(suffix);
+        mark
+    }
+
+    pub(crate) fn pop_path(&mut self, mark: usize) {
+        self.path.truncate(mark);
+    }
+
+    // fails if reading `size` more bytes would go beyond `max_total_bytes`
+    pub(crate) fn check_total(&self, size: usize, offset: usize) -> Result<(), DecodeError> {
+        match self.offset.checked_add(size) {
+            Some(total) if total <= self.opts.max_total_bytes => Ok(()),
+            _ => Err(self.oversized(self.offset as u64 + size as u64, offset)),
+        }
+    }
+
+    pub(crate) fn enter(&mut self) -> Result<(), DecodeError> {
+        if self.depth >= self.opts.max_depth {
+            return Err(DecodeError::TooDeep {
+                depth: self.opts.max_depth,
+                offset: self.offset,
+                path: self.path.clone(),
+            });
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    pub(crate) fn oversized(&self, length: u64, offset: usize) -> DecodeError {
+        DecodeError::OversizedLength {
+            length,
+            offset,
+            path: self.path.clone(),
+        }
+    }
+
+    pub(crate) fn invalid_utf8(&self, offset: usize) -> DecodeError {
+        DecodeError::InvalidUtf8 {
+            offset,
+            path: self.path.clone(),
+        }
+    }
 }
 
-fn read_vi32<R: Read>(buf: &mut R) -> Result<Vec<i32>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let chunk_size: usize = 4;
-    let mut arr = vec![0u8; size * chunk_size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    let ret = arr
-        .chunks(chunk_size)
-        .map(|chunk| {
-            let sized: [u8; 4] = chunk.try_into().expect("slice with incorrect length");
-            i32::from_le_bytes(sized)
-        })
-        .collect();
-    Ok(ret)
+impl<'a> Decoder<&'a [u8]> {
+    // borrows the next `size` bytes from the input
+    pub(crate) fn take(&mut self, size: usize) -> Result<&'a [u8], DecodeError> {
+        let offset = self.offset;
+        self.check_total(size, offset)?;
+        if self.buf.len() < size {
+            return Err(DecodeError::Truncated {
+                offset,
+                path: self.path.clone(),
+            });
+        }
+        let (head, tail) = self.buf.split_at(size);
+        self.buf = tail;
+        self.offset += size;
+        Ok(head)
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.buf.len()
+    }
 }
 
-fn read_vu32<R: Read>(buf: &mut R) -> Result<Vec<u32>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let chunk_size: usize = 4;
-    let mut arr = vec![0u8; size * chunk_size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    let ret = arr
-        .chunks(chunk_size)
-        .map(|chunk| {
-            let sized: [u8; 4] = chunk.try_into().expect("slice with incorrect length");
-            u32::from_le_bytes(sized)
-        })
-        .collect();
-    Ok(ret)
+// reads a u32 length prefix and checks it against `max`
+pub(crate) fn read_len<R: Read>(buf: &mut Decoder<R>, max: usize) -> Result<usize, DecodeError> {
+    let offset = buf.offset;
+    let size_ = read_u32(buf)?;
+    match usize::try_from(size_) {
+        Ok(size) if size <= max => Ok(size),
+        _ => Err(buf.oversized(u64::from(size_), offset)),
+    }
 }
 
-fn read_vi64<R: Read>(buf: &mut R) -> Result<Vec<i64>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let chunk_size: usize = 8;
-    let mut arr = vec![0u8; size * chunk_size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    let ret = arr
-        .chunks(chunk_size)
-        .map(|chunk| {
-            let sized: [u8; 8] = chunk.try_into().expect("slice with incorrect length");
-            i64::from_le_bytes(sized)
-        })
-        .collect();
-    Ok(ret)
+// reads `size` bytes, the total limit is checked before allocating
+fn read_sized<R: Read>(
+    buf: &mut Decoder<R>,
+    size: usize,
+    offset: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    buf.check_total(size, offset)?;
+    let mut arr = vec![0u8; size];
+    buf.read_exact(&mut arr)?;
+    Ok(arr)
 }
 
-fn read_vu64<R: Read>(buf: &mut R) -> Result<Vec<u64>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let chunk_size: usize = 8;
-    let mut arr = vec![0u8; size * chunk_size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    let ret = arr
-        .chunks(chunk_size)
-        .map(|chunk| {
-            let sized: [u8; 8] = chunk.try_into().expect("slice with incorrect length");
-            u64::from_le_bytes(sized)
-        })
-        .collect();
-    Ok(ret)
+// reads a u32 length prefix followed by `size * chunk_size` bytes
+fn read_array<R: Read>(buf: &mut Decoder<R>, chunk_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let offset = buf.offset;
+    let size = read_len(buf, buf.opts.max_vector_len)?;
+    let nbytes = size
+        .checked_mul(chunk_size)
+        .ok_or_else(|| buf.oversized(size as u64 * chunk_size as u64, offset))?;
+    read_sized(buf, nbytes, offset)
 }
 
-fn read_vf32<R: Read>(buf: &mut R) -> Result<Vec<f32>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let chunk_size: usize = 4;
-    let mut arr = vec![0u8; size * chunk_size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    let ret = arr
-        .chunks(chunk_size)
-        .map(|chunk| {
-            let sized: [u8; 4] = chunk.try_into().expect("slice with incorrect length");
-            f32::from_le_bytes(sized)
-        })
-        .collect();
+fn read_vbool<R: Read>(buf: &mut Decoder<R>) -> Result<Vec<bool>, DecodeError> {
+    let arr = read_array(buf, 1)?;
+    let ret = arr.iter().map(|&e| e != 0_u8).collect();
     Ok(ret)
 }
 
-fn read_vf64<R: Read>(buf: &mut R) -> Result<Vec<f64>> {
-    let size_ = read_u32(buf).expect("error reading key size");
-    let size = usize::try_from(size_).unwrap();
-    let chunk_size: usize = 8;
-    let mut arr = vec![0u8; size * chunk_size];
-    buf.read_exact(&mut arr).expect("error reading value");
-    let ret = arr
-        .chunks(chunk_size)
-        .map(|chunk| {
-            let sized: [u8; 8] = chunk.try_into().expect("slice with incorrect length");
-            f64::from_le_bytes(sized)
-        })
-        .collect();
+fn read_vchar<R: Read>(buf: &mut Decoder<R>) -> Result<Vec<char>, DecodeError> {
+    let arr = read_array(buf, 1)?;
+    let ret = arr.iter().map(|&e| e as char).collect();
     Ok(ret)
 }
 
-fn read_u64<R: Read>(buf: &mut R) -> Result<u64> {
+// reads the whole vector with a single `read_exact` into its final buffer
+fn read_vector<R: Read, T: Plain>(buf: &mut Decoder<R>) -> Result<Vec<T>, DecodeError> {
+    let offset = buf.offset;
+    let size = read_len(buf, buf.opts.max_vector_len)?;
+    let chunk_size = std::mem::size_of::<T>();
+    let nbytes = size
+        .checked_mul(chunk_size)
+        .ok_or_else(|| buf.oversized(size as u64 * chunk_size as u64, offset))?;
+    buf.check_total(nbytes, offset)?;
+    let mut arr = vec![T::default(); size];
+    buf.read_exact(as_bytes_mut(&mut arr))?;
+    if cfg!(target_endian = "big") {
+        arr.iter_mut().for_each(|el| *el = el.swap_le());
+    }
+    Ok(arr)
+}
+
+pub(crate) fn read_u64<R: Read>(buf: &mut Decoder<R>) -> Result<u64, DecodeError> {
     let mut buffer = [0u8; 8];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     let num = u64::from_le_bytes(buffer);
     Ok(num)
 }
 
-fn read_i64<R: Read>(buf: &mut R) -> Result<i64> {
+pub(crate) fn read_i64<R: Read>(buf: &mut Decoder<R>) -> Result<i64, DecodeError> {
     let mut buffer = [0u8; 8];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     let num = i64::from_le_bytes(buffer);
     Ok(num)
 }
 
-fn read_u32<R: Read>(buf: &mut R) -> Result<u32> {
+pub(crate) fn read_u32<R: Read>(buf: &mut Decoder<R>) -> Result<u32, DecodeError> {
     let mut buffer = [0u8; 4];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     let num = u32::from_le_bytes(buffer);
     Ok(num)
 }
 
-fn read_i32<R: Read>(buf: &mut R) -> Result<i32> {
+pub(crate) fn read_i32<R: Read>(buf: &mut Decoder<R>) -> Result<i32, DecodeError> {
     let mut buffer = [0u8; 4];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     let num = i32::from_le_bytes(buffer);
     Ok(num)
 }
 
-fn read_u16<R: Read>(buf: &mut R) -> Result<u16> {
+pub(crate) fn read_u16<R: Read>(buf: &mut Decoder<R>) -> Result<u16, DecodeError> {
     let mut buffer = [0u8; 2];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     let num = u16::from_le_bytes(buffer);
     Ok(num)
 }
 
-fn read_i16<R: Read>(buf: &mut R) -> Result<i16> {
+pub(crate) fn read_i16<R: Read>(buf: &mut Decoder<R>) -> Result<i16, DecodeError> {
     let mut buffer = [0u8; 2];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     let num = i16::from_le_bytes(buffer);
     Ok(num)
 }
 
-fn read_char<R: Read>(buf: &mut R) -> Result<char> {
+pub(crate) fn read_char<R: Read>(buf: &mut Decoder<R>) -> Result<char, DecodeError> {
     let mut buffer = [0u8; 1];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     Ok(buffer[0] as char)
 }
 
-fn read_u8<R: Read>(buf: &mut R) -> Result<u8> {
+pub(crate) fn read_u8<R: Read>(buf: &mut Decoder<R>) -> Result<u8, DecodeError> {
     let mut buffer = [0u8; 1];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     Ok(buffer[0])
 }
 
-fn read_i8<R: Read>(buf: &mut R) -> Result<i8> {
+pub(crate) fn read_i8<R: Read>(buf: &mut Decoder<R>) -> Result<i8, DecodeError> {
     let mut buffer = [0u8; 1];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     Ok(buffer[0] as i8)
 }
 
-fn read_bool<R: Read>(buf: &mut R) -> Result<bool> {
+pub(crate) fn read_bool<R: Read>(buf: &mut Decoder<R>) -> Result<bool, DecodeError> {
     let mut buffer = [0u8; 1];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     Ok(buffer[0] != 0)
 }
 
-fn read_f32<R: Read>(buf: &mut R) -> Result<f32> {
+pub(crate) fn read_f32<R: Read>(buf: &mut Decoder<R>) -> Result<f32, DecodeError> {
     let mut buffer = [0u8; 4];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     let num = f32::from_le_bytes(buffer);
     Ok(num)
 }
 
-fn read_f64<R: Read>(buf: &mut R) -> Result<f64> {
+pub(crate) fn read_f64<R: Read>(buf: &mut Decoder<R>) -> Result<f64, DecodeError> {
     let mut buffer = [0u8; 8];
-    buf.read_exact(&mut buffer).expect("error reading value");
+    buf.read_exact(&mut buffer)?;
     let num = f64::from_le_bytes(buffer);
     Ok(num)
 }
 
-fn read_string<R: Read>(buf: &mut R) -> Result<String> {
-    let size_ = read_u32(buf).expect("error reading string size");
-    let size = usize::try_from(size_).unwrap();
-    let mut arr = vec![0u8; size];
-    buf.read_exact(&mut arr).expect("error reading string");
-    let ret = String::from(std::str::from_utf8(&arr).expect("invalid utf-8 sequence"));
-    Ok(ret)
+pub(crate) fn read_cf32<R: Read>(buf: &mut Decoder<R>) -> Result<Complex<f32>, DecodeError> {
+    let re = read_f32(buf)?;
+    let im = read_f32(buf)?;
+    Ok(Complex::new(re, im))
+}
+
+pub(crate) fn read_cf64<R: Read>(buf: &mut Decoder<R>) -> Result<Complex<f64>, DecodeError> {
+    let re = read_f64(buf)?;
+    let im = read_f64(buf)?;
+    Ok(Complex::new(re, im))
+}
+
+// NONE is serialized as a 4 bytes placeholder
+pub(crate) fn read_none<R: Read>(buf: &mut Decoder<R>) -> Result<(), DecodeError> {
+    read_u32(buf)?;
+    Ok(())
+}
+
+fn read_vnone<R: Read>(buf: &mut Decoder<R>) -> Result<usize, DecodeError> {
+    let arr = read_array(buf, 4)?;
+    Ok(arr.len() / 4)
 }
 
-pub fn read_schema<R: Read>(buf: &mut R) -> Result<Schema> {
+fn read_string<R: Read>(buf: &mut Decoder<R>) -> Result<String, DecodeError> {
+    let size = read_len(buf, buf.opts.max_string_len)?;
+    let offset = buf.offset;
+    let arr = read_sized(buf, size, offset - 4)?;
+    String::from_utf8(arr).map_err(|_| buf.invalid_utf8(offset))
+}
+
+pub fn read_schema<R: Read>(buf: &mut R) -> Result<Schema, DecodeError> {
+    read_schema_value(&mut Decoder::new(buf, &DecodeOptions::default()))
+}
+
+fn read_schema_value<R: Read>(buf: &mut Decoder<R>) -> Result<Schema, DecodeError> {
     // read a size
-    read_u32(buf).expect("error reading schema size");
-    Ok(Schema::new(
-        read_key(buf).expect("error reading schema size"),
-        read_hash(buf).expect("error reading schema size"),
-    ))
+    read_u32(buf)?;
+    let class_id = read_key(buf)?;
+    buf.enter()?;
+    let hash = read_hash_value(buf)?;
+    buf.leave();
+    Ok(Schema::new(class_id, hash))
 }
 
-fn read_vstring<R: Read>(buf: &mut R) -> Result<Vec<String>> {
-    let size = read_u32(buf).expect("error reading key size");
+fn read_vstring<R: Read>(buf: &mut Decoder<R>) -> Result<Vec<String>, DecodeError> {
+    let size = read_len(buf, buf.opts.max_vector_len)?;
     let mut arr = Vec::new();
-    for _ in 0..size {
-        arr.push(read_string(buf).expect("error reading string in vector"));
+    for index in 0..size {
+        let mark = buf.push_path_suffix(&format!("[{index}]"));
+        arr.push(read_string(buf)?);
+        buf.pop_path(mark);
     }
     Ok(arr)
 }
 
-fn read_key<R: Read>(buf: &mut R) -> Result<String> {
-    let size = read_u8(buf).expect("error reading key size");
+fn read_key<R: Read>(buf: &mut Decoder<R>) -> Result<String, DecodeError> {
+    let size = read_u8(buf)?;
+    let offset = buf.offset;
     let mut arr = vec![0u8; size.into()];
-    buf.read_exact(&mut arr).expect("error reading key content");
-    let key_name = String::from(std::str::from_utf8(&arr).expect("invalid utf-8 sequence"));
-    Ok(key_name)
+    buf.read_exact(&mut arr)?;
+    String::from_utf8(arr).map_err(|_| buf.invalid_utf8(offset))
 }
 
-fn read_vhash<R: Read>(buf: &mut R) -> Result<Vec<Hash>> {
-    let size = read_u32(buf).expect("error reading key size");
+fn read_vhash<R: Read>(buf: &mut Decoder<R>) -> Result<Vec<Hash>, DecodeError> {
+    let size = read_len(buf, buf.opts.max_vector_len)?;
     let mut arr = Vec::new();
-    for _ in 0..size {
-        arr.push(read_hash(buf).unwrap());
+    buf.enter()?;
+    for index in 0..size {
+        let mark = buf.push_path_suffix(&format!("[{index}]"));
+        arr.push(read_hash_value(buf)?);
+        buf.pop_path(mark);
     }
+    buf.leave();
     Ok(arr)
 }
 
-pub fn read_hash<R: Read>(buf: &mut R) -> Result<Hash> {
+pub fn read_hash<R: Read>(buf: &mut R) -> Result<Hash, DecodeError> {
+    read_hash_with(buf, &DecodeOptions::default())
+}
+
+pub fn read_hash_with<R: Read>(buf: &mut R, opts: &DecodeOptions) -> Result<Hash, DecodeError> {
+    let mut decoder = Decoder::new(buf, opts);
+    decoder.enter()?;
+    read_hash_value(&mut decoder)
+}
+
+fn read_attributes<R: Read>(buf: &mut Decoder<R>) -> Result<Attributes, DecodeError> {
+    let nattrs = read_u32(buf)?;
+    let mut attrs = Attributes::new();
+    for _ in 0..nattrs {
+        let attr_key = read_key(buf)?;
+        let attr_mark = buf.push_path_suffix(&format!("@{attr_key}"));
+        let attr_type = read_u32(buf)?;
+        let attr_value = read_value(buf, attr_type)?;
+        attrs.insert(&attr_key, attr_value);
+        buf.pop_path(attr_mark);
+    }
+    Ok(attrs)
+}
+
+fn read_hash_value<R: Read>(buf: &mut Decoder<R>) -> Result<Hash, DecodeError> {
     let mut hash = Hash::new();
-    let nkeys = read_u32(buf).expect("error reading number of keys");
+    let nkeys = read_u32(buf)?;
     for _ in 0..nkeys {
-        let key = read_key(buf).expect("error reading key");
-        let value_type = read_u32(buf).expect("error reading value type");
-        let nattrs = read_u32(buf).expect("error reading number of attributes");
-        let mut attrs = Attributes::new();
-        for _ in 0..nattrs {
-            let attr_key = read_key(buf).expect("error reading attribute  key");
-            let attr_type = read_u32(buf).expect("error reading attribute type");
-            let attr_value = read_value(buf, attr_type).unwrap();
-            attrs.insert(&attr_key, attr_value);
-        }
-        let value = read_value(buf, value_type).unwrap();
-        hash.insert_attrs(&key, value, attrs);
+        let key = read_key(buf)?;
+        let mark = buf.push_path(&key);
+        let value_type = read_u32(buf)?;
+        let attrs = read_attributes(buf)?;
+        let value = read_value(buf, value_type)?;
+        buf.pop_path(mark);
+        hash.insert_index_attrs(&key, value, attrs);
     }
     Ok(hash)
 }
 
-fn read_value<R: Read>(buf: &mut R, type_: u32) -> Result<HashValue> {
+fn read_value<R: Read>(buf: &mut Decoder<R>, type_: u32) -> Result<HashValue, DecodeError> {
+    match type_ {
+        0 => Ok(HashValue::Bool(read_bool(buf)?)),
+        1 => Ok(HashValue::VectorBool(read_vbool(buf)?)),
+        2 => Ok(HashValue::Char(read_char(buf)?)),
+        3 => Ok(HashValue::VectorChar(read_vchar(buf)?)),
+        4 => Ok(HashValue::Int8(read_i8(buf)?)),
+        5 => Ok(HashValue::VectorInt8(read_vector(buf)?)),
+        6 => Ok(HashValue::UInt8(read_u8(buf)?)),
+        7 => Ok(HashValue::VectorUInt8(read_vector(buf)?)),
+        8 => Ok(HashValue::Int16(read_i16(buf)?)),
+        9 => Ok(HashValue::VectorInt16(read_vector(buf)?)),
+        10 => Ok(HashValue::UInt16(read_u16(buf)?)),
+        11 => Ok(HashValue::VectorUInt16(read_vector(buf)?)),
+        12 => Ok(HashValue::Int32(read_i32(buf)?)),
+        13 => Ok(HashValue::VectorInt32(read_vector(buf)?)),
+        14 => Ok(HashValue::UInt32(read_u32(buf)?)),
+        15 => Ok(HashValue::VectorUInt32(read_vector(buf)?)),
+        16 => Ok(HashValue::Int64(read_i64(buf)?)),
+        17 => Ok(HashValue::VectorInt64(read_vector(buf)?)),
+        18 => Ok(HashValue::UInt64(read_u64(buf)?)),
+        19 => Ok(HashValue::VectorUInt64(read_vector(buf)?)),
+        20 => Ok(HashValue::Float32(read_f32(buf)?)),
+        21 => Ok(HashValue::VectorFloat32(read_vector(buf)?)),
+        22 => Ok(HashValue::Float64(read_f64(buf)?)),
+        23 => Ok(HashValue::VectorFloat64(read_vector(buf)?)),
+        24 => Ok(HashValue::ComplexFloat32(read_cf32(buf)?)),
+        25 => Ok(HashValue::VectorComplexFloat32(read_vector(buf)?)),
+        26 => Ok(HashValue::ComplexFloat64(read_cf64(buf)?)),
+        27 => Ok(HashValue::VectorComplexFloat64(read_vector(buf)?)),
+        28 => Ok(HashValue::String(read_string(buf)?)),
+        29 => Ok(HashValue::VectorString(read_vstring(buf)?)),
+        30 => {
+            buf.enter()?;
+            let hash = read_hash_value(buf)?;
+            buf.leave();
+            Ok(HashValue::Hash(hash))
+        }
+        31 => Ok(HashValue::VectorHash(read_vhash(buf)?)),
+        32 => Ok(HashValue::Schema(read_schema_value(buf)?)),
+        34 => read_none(buf).map(|_| HashValue::None),
+        35 => Ok(HashValue::VectorNone(read_vnone(buf)?)),
+        37 => Ok(HashValue::ByteArray(read_array(buf, 1)?)),
+        _ => Err(DecodeError::UnknownType {
+            type_,
+            offset: buf.offset,
+            path: buf.path.clone(),
+        }),
+    }
+}
+
+// Size in bytes of the fixed size types
+fn fixed_size(type_: u32) -> Option<usize> {
     match type_ {
-        0 => Ok(HashValue::Bool(read_bool(buf).unwrap())),
-        1 => Ok(HashValue::VectorBool(read_vbool(buf).unwrap())),
-        2 => Ok(HashValue::Char(read_char(buf).unwrap())),
-        3 => Ok(HashValue::VectorChar(read_vchar(buf).unwrap())),
-        4 => Ok(HashValue::Int8(read_i8(buf).unwrap())),
-        5 => Ok(HashValue::VectorInt8(read_vi8(buf).unwrap())),
-        6 => Ok(HashValue::UInt8(read_u8(buf).unwrap())),
-        7 => Ok(HashValue::VectorUInt8(read_vu8(buf).unwrap())),
-        8 => Ok(HashValue::Int16(read_i16(buf).unwrap())),
-        9 => Ok(HashValue::VectorInt16(read_vi16(buf).unwrap())),
-        10 => Ok(HashValue::UInt16(read_u16(buf).unwrap())),
-        11 => Ok(HashValue::VectorUInt16(read_vu16(buf).unwrap())),
-        12 => Ok(HashValue::Int32(read_i32(buf).unwrap())),
-        13 => Ok(HashValue::VectorInt32(read_vi32(buf).unwrap())),
-        14 => Ok(HashValue::UInt32(read_u32(buf).unwrap())),
-        15 => Ok(HashValue::VectorUInt32(read_vu32(buf).unwrap())),
-        16 => Ok(HashValue::Int64(read_i64(buf).unwrap())),
-        17 => Ok(HashValue::VectorInt64(read_vi64(buf).unwrap())),
-        18 => Ok(HashValue::UInt64(read_u64(buf).unwrap())),
-        19 => Ok(HashValue::VectorUInt64(read_vu64(buf).unwrap())),
-        20 => Ok(HashValue::Float32(read_f32(buf).unwrap())),
-        21 => Ok(HashValue::VectorFloat32(read_vf32(buf).unwrap())),
-        22 => Ok(HashValue::Float64(read_f64(buf).unwrap())),
-        23 => Ok(HashValue::VectorFloat64(read_vf64(buf).unwrap())),
-        28 => Ok(HashValue::String(read_string(buf).unwrap())),
-        29 => Ok(HashValue::VectorString(read_vstring(buf).unwrap())),
-        30 => Ok(HashValue::Hash(read_hash(buf).unwrap())),
-        31 => Ok(HashValue::VectorHash(read_vhash(buf).unwrap())),
-        32 => Ok(HashValue::Schema(read_schema(buf).unwrap())),
-        _ => panic!("Type {type_} not implemented"),
+        0 | 2 | 4 | 6 => Some(1),
+        8 | 10 => Some(2),
+        12 | 14 | 20 | 34 => Some(4),
+        16 | 18 | 22 | 24 => Some(8),
+        26 => Some(16),
+        _ => None,
     }
 }
+
+// Size in bytes of the elements of the vector types
+fn element_size(type_: u32) -> Option<usize> {
+    match type_ {
+        1 | 3 | 5 | 7 | 37 => Some(1),
+        9 | 11 => Some(2),
+        13 | 15 | 21 | 35 => Some(4),
+        17 | 19 | 23 | 25 => Some(8),
+        27 => Some(16),
+        _ => None,
+    }
+}
+
+fn skip_key<R: Read>(buf: &mut Decoder<R>) -> Result<(), DecodeError> {
+    let size = read_u8(buf)?;
+    buf.skip(size.into())
+}
+
+fn skip_string<R: Read>(buf: &mut Decoder<R>) -> Result<(), DecodeError> {
+    let size = read_len(buf, buf.opts.max_string_len)?;
+    buf.skip(size)
+}
+
+fn skip_attributes<R: Read>(buf: &mut Decoder<R>) -> Result<(), DecodeError> {
+    let nattrs = read_u32(buf)?;
+    for _ in 0..nattrs {
+        skip_key(buf)?;
+        let attr_type = read_u32(buf)?;
+        skip_value(buf, attr_type)?;
+    }
+    Ok(())
+}
+
+fn skip_hash<R: Read>(buf: &mut Decoder<R>) -> Result<(), DecodeError> {
+    buf.enter()?;
+    let nkeys = read_u32(buf)?;
+    for _ in 0..nkeys {
+        skip_key(buf)?;
+        let value_type = read_u32(buf)?;
+        skip_attributes(buf)?;
+        skip_value(buf, value_type)?;
+    }
+    buf.leave();
+    Ok(())
+}
+
+// Moves past a value using the length prefixes, nothing is allocated
+fn skip_value<R: Read>(buf: &mut Decoder<R>, type_: u32) -> Result<(), DecodeError> {
+    if let Some(size) = fixed_size(type_) {
+        return buf.skip(size);
+    }
+    if let Some(size) = element_size(type_) {
+        let offset = buf.offset;
+        let len = read_len(buf, buf.opts.max_vector_len)?;
+        let nbytes = len
+            .checked_mul(size)
+            .ok_or_else(|| buf.oversized(len as u64 * size as u64, offset))?;
+        return buf.skip(nbytes);
+    }
+    match type_ {
+        28 => skip_string(buf),
+        29 => {
+            let len = read_len(buf, buf.opts.max_vector_len)?;
+            for _ in 0..len {
+                skip_string(buf)?;
+            }
+            Ok(())
+        }
+        30 => skip_hash(buf),
+        31 => {
+            let len = read_len(buf, buf.opts.max_vector_len)?;
+            for _ in 0..len {
+                skip_hash(buf)?;
+            }
+            Ok(())
+        }
+        32 => {
+            let size = read_u32(buf)?;
+            buf.skip(size as usize)
+        }
+        _ => Err(DecodeError::UnknownType {
+            type_,
+            offset: buf.offset,
+            path: buf.path.clone(),
+        }),
+    }
+}
+
+enum Selection {
+    // the node is selected, or is below a selected path
+    All,
+    // a selected path is below the node
+    Partial,
+    None,
+}
+
+fn select(paths: &[&str], path: &str) -> Selection {
+    let mut selection = Selection::None;
+    for selected in paths {
+        match selected.strip_prefix(path) {
+            Some("") => return Selection::All,
+            Some(rest) if rest.starts_with('.') => selection = Selection::Partial,
+            _ => match path.strip_prefix(selected) {
+                Some(rest) if rest.starts_with('.') => return Selection::All,
+                _ => {}
+            },
+        }
+    }
+    selection
+}
+
+// Decodes only the nodes matching, or below, one of the dotted `paths`.
+// The hashes leading to a selected path are created with the selected
+// children only, everything else is skipped.
+pub fn read_hash_selected<R: Read>(buf: &mut R, paths: &[&str]) -> Result<Hash, DecodeError> {
+    read_hash_selected_with(buf, paths, &DecodeOptions::default())
+}
+
+pub fn read_hash_selected_with<R: Read>(
+    buf: &mut R,
+    paths: &[&str],
+    opts: &DecodeOptions,
+) -> Result<Hash, DecodeError> {
+    let mut decoder = Decoder::new(buf, opts);
+    decoder.enter()?;
+    read_hash_selected_value(&mut decoder, paths)
+}
+
+fn read_hash_selected_value<R: Read>(
+    buf: &mut Decoder<R>,
+    paths: &[&str],
+) -> Result<Hash, DecodeError> {
+    let mut hash = Hash::new();
+    let nkeys = read_u32(buf)?;
+    for _ in 0..nkeys {
+        let key = read_key(buf)?;
+        let mark = buf.push_path(&key);
+        let value_type = read_u32(buf)?;
+        match select(paths, &buf.path) {
+            Selection::All => {
+                let attrs = read_attributes(buf)?;
+                let value = read_value(buf, value_type)?;
+                hash.insert_index_attrs(&key, value, attrs);
+            }
+            Selection::Partial if value_type == 30 => {
+                let attrs = read_attributes(buf)?;
+                buf.enter()?;
+                let value = read_hash_selected_value(buf, paths)?;
+                buf.leave();
+                hash.insert_index_attrs(&key, HashValue::Hash(value), attrs);
+            }
+            _ => {
+                skip_attributes(buf)?;
+                skip_value(buf, value_type)?;
+            }
+        }
+        buf.pop_path(mark);
+    }
+    Ok(hash)
+}
@@ -1,220 +1,332 @@
-use std::io::{Cursor, Result, Write};
+use std::fmt;
+use std::io::Write;
 
+use crate::bulk::{as_bytes, Plain};
 use crate::hash::Hash;
 use crate::schema::Schema;
 use crate::types::{get_hashtype, HashValue};
 
-fn write_string<W: Write>(buf: &mut W, s: &String) -> Result<usize> {
-    let size = s.len() as u32;
-    let mut size = buf.write(&size.to_le_bytes()).unwrap();
-    size += buf.write(s.as_bytes()).unwrap();
-    Ok(size)
+#[derive(Debug)]
+pub enum EncodeError {
+    // keys are prefixed by a single byte length
+    KeyTooLong { key: String },
+    // vectors, strings and hashes are prefixed by a u32 length
+    CollectionTooLarge { length: usize },
+    Io(std::io::Error),
 }
 
-fn write_key<W: Write>(buf: &mut W, s: &String) -> Result<usize> {
-    let size = s.len() as u8;
-    let mut size = buf.write(&[size]).unwrap();
-    size += buf.write(s.as_bytes()).unwrap();
-    Ok(size)
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodeError::KeyTooLong { key } => {
+                write!(f, "key '{}' is {} bytes long, max is 255", key, key.len())
+            }
+            EncodeError::CollectionTooLarge { length } => {
+                write!(f, "length {length} does not fit in 32 bits")
+            }
+            EncodeError::Io(err) => write!(f, "io error {err}"),
+        }
+    }
 }
 
-fn write_vec_u8<W: Write>(buf: &mut W, value: &Vec<u8>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
-    for el in value {
-        size += buf.write(&el.to_le_bytes()).unwrap();
+impl std::error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EncodeError::Io(err) => Some(err),
+            _ => None,
+        }
     }
-    Ok(size)
 }
 
-fn write_vec_bool<W: Write>(buf: &mut W, value: &Vec<bool>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
-    for el in value {
-        size += buf.write(&u8::from(*el).to_le_bytes()).unwrap();
+impl From<std::io::Error> for EncodeError {
+    fn from(err: std::io::Error) -> Self {
+        EncodeError::Io(err)
     }
-    Ok(size)
 }
 
-fn write_vec_char<W: Write>(buf: &mut W, value: &Vec<char>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
-    for el in value {
-        let a = *el as u8;
-        size += buf.write(&a.to_le_bytes()).unwrap();
+// Number of bytes a hash or a value takes once encoded, can be used to
+// allocate exact buffers
+pub trait SerializedLen {
+    fn serialized_len(&self) -> usize;
+}
+
+pub fn serialized_len<T: SerializedLen>(value: &T) -> usize {
+    value.serialized_len()
+}
+
+impl SerializedLen for Hash {
+    fn serialized_len(&self) -> usize {
+        let mut size = 4;
+        for (key, value, attrs) in self {
+            size += 1 + key.len() + 4 + 4;
+            for (attr_key, attr_value) in attrs {
+                size += 1 + attr_key.len() + 4 + attr_value.serialized_len();
+            }
+            size += value.serialized_len();
+        }
+        size
     }
-    Ok(size)
 }
 
-fn write_vec_i8<W: Write>(buf: &mut W, value: &Vec<i8>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
-    for el in value {
-        size += buf.write(&el.to_le_bytes()).unwrap();
+impl SerializedLen for HashValue {
+    fn serialized_len(&self) -> usize {
+        match self {
+            HashValue::Bool(_) | HashValue::Char(_) | HashValue::Int8(_) | HashValue::UInt8(_) => 1,
+            HashValue::Int16(_) | HashValue::UInt16(_) => 2,
+            HashValue::Int32(_) | HashValue::UInt32(_) | HashValue::Float32(_) => 4,
+            HashValue::Int64(_)
+            | HashValue::UInt64(_)
+            | HashValue::Float64(_)
+            | HashValue::ComplexFloat32(_) => 8,
+            HashValue::ComplexFloat64(_) => 16,
+            HashValue::VectorBool(x) => 4 + x.len(),
+            HashValue::VectorChar(x) => 4 + x.len(),
+            HashValue::VectorInt8(x) => 4 + x.len(),
+            HashValue::VectorUInt8(x) => 4 + x.len(),
+            HashValue::VectorInt16(x) => 4 + 2 * x.len(),
+            HashValue::VectorUInt16(x) => 4 + 2 * x.len(),
+            HashValue::VectorInt32(x) => 4 + 4 * x.len(),
+            HashValue::VectorUInt32(x) => 4 + 4 * x.len(),
+            HashValue::VectorFloat32(x) => 4 + 4 * x.len(),
+            HashValue::VectorInt64(x) => 4 + 8 * x.len(),
+            HashValue::VectorUInt64(x) => 4 + 8 * x.len(),
+            HashValue::VectorFloat64(x) => 4 + 8 * x.len(),
+            HashValue::VectorComplexFloat32(x) => 4 + 8 * x.len(),
+            HashValue::VectorComplexFloat64(x) => 4 + 16 * x.len(),
+            HashValue::String(x) => 4 + x.len(),
+            HashValue::VectorString(x) => 4 + x.iter().map(|s| 4 + s.len()).sum::<usize>(),
+            HashValue::Hash(x) => x.serialized_len(),
+            HashValue::VectorHash(x) => 4 + x.iter().map(|h| h.serialized_len()).sum::<usize>(),
+            HashValue::Schema(x) => 4 + schema_content_len(x),
+            HashValue::None => 4,
+            HashValue::VectorNone(x) => 4 + 4 * x,
+            HashValue::ByteArray(x) => 4 + x.len(),
+        }
     }
-    Ok(size)
 }
 
-fn write_vec_i16<W: Write>(buf: &mut W, value: &Vec<i16>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
-    for el in value {
-        size += buf.write(&el.to_le_bytes()).unwrap();
+// the schema is prefixed by the size of its class id and hash
+fn schema_content_len(schema: &Schema) -> usize {
+    1 + schema.class_id.len() + schema.hash.serialized_len()
+}
+
+fn check_size(length: usize) -> Result<(), EncodeError> {
+    u32::try_from(length).map_err(|_| EncodeError::CollectionTooLarge { length })?;
+    Ok(())
+}
+
+fn check_key(key: &str) -> Result<(), EncodeError> {
+    if key.len() > usize::from(u8::MAX) {
+        return Err(EncodeError::KeyTooLong {
+            key: key.to_string(),
+        });
     }
-    Ok(size)
+    Ok(())
 }
 
-fn write_vec_u16<W: Write>(buf: &mut W, value: &Vec<u16>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
-    for el in value {
-        size += buf.write(&el.to_le_bytes()).unwrap();
+// runs the checks `write_value` does while streaming, without writing
+fn check_value(value: &HashValue) -> Result<(), EncodeError> {
+    match value {
+        HashValue::VectorBool(x) => check_size(x.len()),
+        HashValue::VectorChar(x) => check_size(x.len()),
+        HashValue::VectorInt8(x) => check_size(x.len()),
+        HashValue::VectorUInt8(x) => check_size(x.len()),
+        HashValue::VectorInt16(x) => check_size(x.len()),
+        HashValue::VectorUInt16(x) => check_size(x.len()),
+        HashValue::VectorInt32(x) => check_size(x.len()),
+        HashValue::VectorUInt32(x) => check_size(x.len()),
+        HashValue::VectorInt64(x) => check_size(x.len()),
+        HashValue::VectorUInt64(x) => check_size(x.len()),
+        HashValue::VectorFloat32(x) => check_size(x.len()),
+        HashValue::VectorFloat64(x) => check_size(x.len()),
+        HashValue::VectorComplexFloat32(x) => check_size(x.len()),
+        HashValue::VectorComplexFloat64(x) => check_size(x.len()),
+        HashValue::ByteArray(x) => check_size(x.len()),
+        HashValue::String(x) => check_size(x.len()),
+        HashValue::VectorString(x) => {
+            check_size(x.len())?;
+            x.iter().try_for_each(|s| check_size(s.len()))
+        }
+        HashValue::Hash(x) => check_hash(x),
+        HashValue::VectorHash(x) => {
+            check_size(x.len())?;
+            x.iter().try_for_each(check_hash)
+        }
+        HashValue::Schema(x) => {
+            check_size(schema_content_len(x))?;
+            check_key(&x.class_id)?;
+            check_hash(&x.hash)
+        }
+        HashValue::VectorNone(x) => check_size(*x),
+        _ => Ok(()),
     }
-    Ok(size)
 }
 
-fn write_vec_i32<W: Write>(buf: &mut W, value: &Vec<i32>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
-    for el in value {
-        size += buf.write(&el.to_le_bytes()).unwrap();
+fn check_hash(hash: &Hash) -> Result<(), EncodeError> {
+    check_size(hash.len())?;
+    for (key, value, attrs) in hash {
+        check_key(key)?;
+        check_size(attrs.len())?;
+        for (attr_key, attr_value) in attrs {
+            check_key(attr_key)?;
+            check_value(attr_value)?;
+        }
+        check_value(value)?;
     }
+    Ok(())
+}
+
+fn write_bytes<W: Write>(buf: &mut W, value: &[u8]) -> Result<usize, EncodeError> {
+    buf.write_all(value)?;
+    Ok(value.len())
+}
+
+fn write_size<W: Write>(buf: &mut W, length: usize) -> Result<usize, EncodeError> {
+    let size = u32::try_from(length).map_err(|_| EncodeError::CollectionTooLarge { length })?;
+    write_bytes(buf, &size.to_le_bytes())
+}
+
+fn write_string<W: Write>(buf: &mut W, s: &str) -> Result<usize, EncodeError> {
+    let mut size = write_size(buf, s.len())?;
+    size += write_bytes(buf, s.as_bytes())?;
     Ok(size)
 }
 
-fn write_vec_u32<W: Write>(buf: &mut W, value: &Vec<u32>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
-    for el in value {
-        size += buf.write(&el.to_le_bytes()).unwrap();
-    }
+fn write_key<W: Write>(buf: &mut W, s: &str) -> Result<usize, EncodeError> {
+    let size = u8::try_from(s.len()).map_err(|_| EncodeError::KeyTooLong { key: s.to_string() })?;
+    let mut size = write_bytes(buf, &[size])?;
+    size += write_bytes(buf, s.as_bytes())?;
     Ok(size)
 }
 
-fn write_vec_i64<W: Write>(buf: &mut W, value: &Vec<i64>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
-    for el in value {
-        size += buf.write(&el.to_le_bytes()).unwrap();
+fn write_scalar<W: Write, T: Plain>(buf: &mut W, value: T) -> Result<usize, EncodeError> {
+    write_bytes(buf, as_bytes(&[value.swap_le()]))
+}
+
+// writes the whole vector with a single `write_all` on little endian targets
+fn write_vector<W: Write, T: Plain>(buf: &mut W, value: &[T]) -> Result<usize, EncodeError> {
+    let mut size = write_size(buf, value.len())?;
+    if cfg!(target_endian = "little") {
+        size += write_bytes(buf, as_bytes(value))?;
+    } else {
+        for el in value {
+            size += write_bytes(buf, as_bytes(&[el.swap_le()]))?;
+        }
     }
     Ok(size)
 }
 
-fn write_vec_u64<W: Write>(buf: &mut W, value: &Vec<u64>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
+fn write_vec_bool<W: Write>(buf: &mut W, value: &[bool]) -> Result<usize, EncodeError> {
+    let mut size = write_size(buf, value.len())?;
     for el in value {
-        size += buf.write(&el.to_le_bytes()).unwrap();
+        size += write_bytes(buf, &u8::from(*el).to_le_bytes())?;
     }
     Ok(size)
 }
 
-fn write_vec_f32<W: Write>(buf: &mut W, value: &Vec<f32>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
+fn write_vec_char<W: Write>(buf: &mut W, value: &[char]) -> Result<usize, EncodeError> {
+    let mut size = write_size(buf, value.len())?;
     for el in value {
-        size += buf.write(&el.to_le_bytes()).unwrap();
+        let a = *el as u8;
+        size += write_bytes(buf, &a.to_le_bytes())?;
     }
     Ok(size)
 }
 
-fn write_vec_f64<W: Write>(buf: &mut W, value: &Vec<f64>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
-    for el in value {
-        size += buf.write(&el.to_le_bytes()).unwrap();
+fn write_vnone<W: Write>(buf: &mut W, value: usize) -> Result<usize, EncodeError> {
+    let mut size = write_size(buf, value)?;
+    for _ in 0..value {
+        size += write_bytes(buf, &0u32.to_le_bytes())?;
     }
     Ok(size)
 }
 
-fn write_vstring<W: Write>(buf: &mut W, value: &Vec<String>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
+fn write_vstring<W: Write>(buf: &mut W, value: &[String]) -> Result<usize, EncodeError> {
+    let mut size = write_size(buf, value.len())?;
     for el in value {
-        size += write_string(buf, el).unwrap();
+        size += write_string(buf, el)?;
     }
     Ok(size)
 }
 
-fn write_vhash<W: Write>(buf: &mut W, value: &Vec<Hash>) -> Result<usize> {
-    let vsize = value.len() as u32;
-    let mut size = buf.write(&vsize.to_le_bytes()).unwrap();
+fn write_vhash<W: Write>(buf: &mut W, value: &[Hash]) -> Result<usize, EncodeError> {
+    let mut size = write_size(buf, value.len())?;
     for el in value {
-        size += write_hash(buf, el).unwrap();
+        size += write_hash_unchecked(buf, el)?;
     }
     Ok(size)
 }
 
-fn write_schema<W: Write>(buf: &mut W, schema: &Schema) -> Result<usize> {
-    let mut c = Cursor::new(Vec::new());
-    let mut size = write_key(&mut c, &schema.class_id).unwrap();
-    size += write_hash(&mut c, &schema.hash).unwrap();
-    let size32 = size as u32;
-    let mut size2 = buf.write(&size32.to_le_bytes()).unwrap();
-    size2 += buf.write(&c.into_inner()[..]).unwrap();
-    Ok(size2)
+fn write_schema<W: Write>(buf: &mut W, schema: &Schema) -> Result<usize, EncodeError> {
+    let mut size = write_size(buf, schema_content_len(schema))?;
+    size += write_key(buf, &schema.class_id)?;
+    size += write_hash_unchecked(buf, &schema.hash)?;
+    Ok(size)
 }
 
-fn write_value<W: Write>(buf: &mut W, value: &HashValue) -> Result<usize> {
+fn write_value<W: Write>(buf: &mut W, value: &HashValue) -> Result<usize, EncodeError> {
     match value {
-        HashValue::Bool(x) => {
-            if *x {
-                return buf.write(&[1_u8]);
-            }
-            buf.write(&[0_u8])
-        }
+        HashValue::Bool(x) => write_bytes(buf, &[u8::from(*x)]),
         HashValue::VectorBool(x) => write_vec_bool(buf, x),
-        HashValue::Char(x) => buf.write(&[*x as u8]),
+        HashValue::Char(x) => write_bytes(buf, &[*x as u8]),
         HashValue::VectorChar(x) => write_vec_char(buf, x),
-        HashValue::Int8(x) => buf.write(&[*x as u8]),
-        HashValue::VectorInt8(x) => write_vec_i8(buf, x),
-        HashValue::UInt8(x) => buf.write(&[*x]),
-        HashValue::VectorUInt8(x) => write_vec_u8(buf, x),
-        HashValue::Int16(x) => buf.write(&x.to_le_bytes()),
-        HashValue::VectorInt16(x) => write_vec_i16(buf, x),
-        HashValue::UInt16(x) => buf.write(&x.to_le_bytes()),
-        HashValue::VectorUInt16(x) => write_vec_u16(buf, x),
-        HashValue::Int32(x) => buf.write(&x.to_le_bytes()),
-        HashValue::VectorInt32(x) => write_vec_i32(buf, x),
-        HashValue::UInt32(x) => buf.write(&x.to_le_bytes()),
-        HashValue::VectorUInt32(x) => write_vec_u32(buf, x),
-        HashValue::Int64(x) => buf.write(&x.to_le_bytes()),
-        HashValue::VectorInt64(x) => write_vec_i64(buf, x),
-        HashValue::UInt64(x) => buf.write(&x.to_le_bytes()),
-        HashValue::VectorUInt64(x) => write_vec_u64(buf, x),
-        HashValue::Float32(x) => buf.write(&x.to_le_bytes()),
-        HashValue::VectorFloat32(x) => write_vec_f32(buf, x),
-        HashValue::Float64(x) => buf.write(&x.to_le_bytes()),
-        HashValue::VectorFloat64(x) => write_vec_f64(buf, x),
+        HashValue::Int8(x) => write_bytes(buf, &[*x as u8]),
+        HashValue::VectorInt8(x) => write_vector(buf, x),
+        HashValue::UInt8(x) => write_bytes(buf, &[*x]),
+        HashValue::VectorUInt8(x) => write_vector(buf, x),
+        HashValue::Int16(x) => write_bytes(buf, &x.to_le_bytes()),
+        HashValue::VectorInt16(x) => write_vector(buf, x),
+        HashValue::UInt16(x) => write_bytes(buf, &x.to_le_bytes()),
+        HashValue::VectorUInt16(x) => write_vector(buf, x),
+        HashValue::Int32(x) => write_bytes(buf, &x.to_le_bytes()),
+        HashValue::VectorInt32(x) => write_vector(buf, x),
+        HashValue::UInt32(x) => write_bytes(buf, &x.to_le_bytes()),
+        HashValue::VectorUInt32(x) => write_vector(buf, x),
+        HashValue::Int64(x) => write_bytes(buf, &x.to_le_bytes()),
+        HashValue::VectorInt64(x) => write_vector(buf, x),
+        HashValue::UInt64(x) => write_bytes(buf, &x.to_le_bytes()),
+        HashValue::VectorUInt64(x) => write_vector(buf, x),
+        HashValue::Float32(x) => write_bytes(buf, &x.to_le_bytes()),
+        HashValue::VectorFloat32(x) => write_vector(buf, x),
+        HashValue::Float64(x) => write_bytes(buf, &x.to_le_bytes()),
+        HashValue::VectorFloat64(x) => write_vector(buf, x),
+        HashValue::ComplexFloat32(x) => write_scalar(buf, *x),
+        HashValue::VectorComplexFloat32(x) => write_vector(buf, x),
+        HashValue::ComplexFloat64(x) => write_scalar(buf, *x),
+        HashValue::VectorComplexFloat64(x) => write_vector(buf, x),
         HashValue::String(x) => write_string(buf, x),
         HashValue::VectorString(x) => write_vstring(buf, x),
-        HashValue::Hash(x) => write_hash(buf, x),
+        HashValue::Hash(x) => write_hash_unchecked(buf, x),
         HashValue::VectorHash(x) => write_vhash(buf, x),
         HashValue::Schema(x) => write_schema(buf, x),
+        HashValue::None => write_bytes(buf, &0u32.to_le_bytes()),
+        HashValue::VectorNone(x) => write_vnone(buf, *x),
+        HashValue::ByteArray(x) => write_vector(buf, x),
     }
 }
 
-pub fn write_hash<W: Write>(buf: &mut W, hash: &Hash) -> Result<usize> {
-    let nkeys = hash.len();
-    let nkeys_32 = nkeys as u32;
-    let mut size = buf.write(&nkeys_32.to_le_bytes()).unwrap();
-    // TODO: figure out how to iterate a Hash
-    for index in 0..nkeys {
-        let node = hash.get_index(index).unwrap();
-        size += write_key(buf, &node.key).unwrap();
-        let type_ = get_hashtype(&node.value);
-        size += buf.write(&type_.to_le_bytes()).unwrap();
-        let nattrs = node.attrs.len();
-        let nattrs_32 = nattrs as u32;
-        size += buf.write(&nattrs_32.to_le_bytes()).unwrap();
-        for attr_index in 0..nattrs {
-            let attr = node.attrs.get_index(attr_index).unwrap();
-            let attr_type = get_hashtype(&attr.value);
-            size += write_key(buf, &attr.key).unwrap();
-            size += buf.write(&attr_type.to_le_bytes()).unwrap();
-            size += write_value(buf, &attr.value).unwrap();
+// The whole hash is checked before anything is written, a key or a
+// collection that does not fit leaves `buf` untouched. Only an `Io` error
+// can leave a partial frame behind.
+pub fn write_hash<W: Write>(buf: &mut W, hash: &Hash) -> Result<usize, EncodeError> {
+    check_hash(hash)?;
+    write_hash_unchecked(buf, hash)
+}
+
+fn write_hash_unchecked<W: Write>(buf: &mut W, hash: &Hash) -> Result<usize, EncodeError> {
+    let mut size = write_size(buf, hash.len())?;
+    for (key, value, attrs) in hash {
+        size += write_key(buf, key)?;
+        let type_ = get_hashtype(value);
+        size += write_bytes(buf, &type_.to_le_bytes())?;
+        size += write_size(buf, attrs.len())?;
+        for (attr_key, attr_value) in attrs {
+            let attr_type = get_hashtype(attr_value);
+            size += write_key(buf, attr_key)?;
+            size += write_bytes(buf, &attr_type.to_le_bytes())?;
+            size += write_value(buf, attr_value)?;
         }
-        size += write_value(buf, &node.value).unwrap();
+        size += write_value(buf, value)?;
     }
     Ok(size)
 }
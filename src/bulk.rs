@@ -0,0 +1,65 @@
+use crate::types::Complex;
+
+/// Numeric types valid for any bit pattern, vectors of them are read and
+/// written as a single block of bytes. On big endian targets every element
+/// is byte swapped after reading and before writing.
+///
+/// # Safety
+///
+/// Implementors must have no padding and no invalid bit patterns.
+pub(crate) unsafe trait Plain: Copy + Default {
+    // converts between the little endian wire representation and the native one
+    fn swap_le(self) -> Self;
+}
+
+macro_rules! plain_int {
+    ($($t:ty),*) => {$(
+        unsafe impl Plain for $t {
+            #[inline]
+            fn swap_le(self) -> Self {
+                <$t>::from_le(self)
+            }
+        }
+    )*};
+}
+
+plain_int!(i8, u8, i16, u16, i32, u32, i64, u64);
+
+unsafe impl Plain for f32 {
+    #[inline]
+    fn swap_le(self) -> Self {
+        f32::from_bits(u32::from_le(self.to_bits()))
+    }
+}
+
+unsafe impl Plain for f64 {
+    #[inline]
+    fn swap_le(self) -> Self {
+        f64::from_bits(u64::from_le(self.to_bits()))
+    }
+}
+
+// `Complex` is `repr(C)` with two fields of the same type
+unsafe impl<T: Plain> Plain for Complex<T> {
+    #[inline]
+    fn swap_le(self) -> Self {
+        Complex::new(self.re.swap_le(), self.im.swap_le())
+    }
+}
+
+pub(crate) fn as_bytes<T: Plain>(values: &[T]) -> &[u8] {
+    // Safety: `T` has no padding, every byte of the slice is initialized
+    unsafe {
+        std::slice::from_raw_parts(values.as_ptr() as *const u8, std::mem::size_of_val(values))
+    }
+}
+
+pub(crate) fn as_bytes_mut<T: Plain>(values: &mut [T]) -> &mut [u8] {
+    // Safety: `T` has no padding and accepts any bit pattern
+    unsafe {
+        std::slice::from_raw_parts_mut(
+            values.as_mut_ptr() as *mut u8,
+            std::mem::size_of_val(values),
+        )
+    }
+}
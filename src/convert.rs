@@ -0,0 +1,606 @@
+use crate::hash::Hash;
+use crate::schema::Schema;
+use crate::types::{Complex, HashValue, TypeError};
+
+// `From<T> for HashValue` and `TryFrom<&HashValue>` back for the scalar type
+// and its vector. Vectors can be borrowed as slices as well.
+macro_rules! convert {
+    ($($t:ty => $scalar:ident, $vector:ident, $name:literal);* $(;)?) => {$(
+        impl From<$t> for HashValue {
+            fn from(value: $t) -> Self {
+                HashValue::$scalar(value)
+            }
+        }
+
+        impl TryFrom<&HashValue> for $t {
+            type Error = TypeError;
+
+            fn try_from(value: &HashValue) -> Result<Self, TypeError> {
+                match value {
+                    HashValue::$scalar(x) => Ok(*x),
+                    _ => Err(TypeError::mismatch($name, value)),
+                }
+            }
+        }
+
+        impl From<Vec<$t>> for HashValue {
+            fn from(value: Vec<$t>) -> Self {
+                HashValue::$vector(value)
+            }
+        }
+
+        impl From<&[$t]> for HashValue {
+            fn from(value: &[$t]) -> Self {
+                HashValue::$vector(value.to_vec())
+            }
+        }
+
+        impl<'a> TryFrom<&'a HashValue> for &'a [$t] {
+            type Error = TypeError;
+
+            fn try_from(value: &'a HashValue) -> Result<Self, TypeError> {
+                match value {
+                    HashValue::$vector(x) => Ok(x),
+                    _ => Err(TypeError::mismatch(concat!("VECTOR_", $name), value)),
+                }
+            }
+        }
+
+        impl TryFrom<&HashValue> for Vec<$t> {
+            type Error = TypeError;
+
+            fn try_from(value: &HashValue) -> Result<Self, TypeError> {
+                <&[$t]>::try_from(value).map(|x| x.to_vec())
+            }
+        }
+    )*};
+}
+
+convert!(
+    bool => Bool, VectorBool, "BOOL";
+    char => Char, VectorChar, "CHAR";
+    i8 => Int8, VectorInt8, "INT8";
+    i16 => Int16, VectorInt16, "INT16";
+    u16 => UInt16, VectorUInt16, "UINT16";
+    i32 => Int32, VectorInt32, "INT32";
+    u32 => UInt32, VectorUInt32, "UINT32";
+    i64 => Int64, VectorInt64, "INT64";
+    u64 => UInt64, VectorUInt64, "UINT64";
+    f32 => Float32, VectorFloat32, "FLOAT";
+    f64 => Float64, VectorFloat64, "DOUBLE";
+    Complex<f32> => ComplexFloat32, VectorComplexFloat32, "COMPLEX_FLOAT";
+    Complex<f64> => ComplexFloat64, VectorComplexFloat64, "COMPLEX_DOUBLE";
+);
+
+// bytes are a `VECTOR_UINT8`, a `BYTE_ARRAY` can be read as well
+impl From<u8> for HashValue {
+    fn from(value: u8) -> Self {
+        HashValue::UInt8(value)
+    }
+}
+
+impl TryFrom<&HashValue> for u8 {
+    type Error = TypeError;
+
+    fn try_from(value: &HashValue) -> Result<Self, TypeError> {
+        match value {
+            HashValue::UInt8(x) => Ok(*x),
+            _ => Err(TypeError::mismatch("UINT8", value)),
+        }
+    }
+}
+
+impl From<Vec<u8>> for HashValue {
+    fn from(value: Vec<u8>) -> Self {
+        HashValue::VectorUInt8(value)
+    }
+}
+
+impl From<&[u8]> for HashValue {
+    fn from(value: &[u8]) -> Self {
+        HashValue::VectorUInt8(value.to_vec())
+    }
+}
+
+impl<'a> TryFrom<&'a HashValue> for &'a [u8] {
+    type Error = TypeError;
+
+    fn try_from(value: &'a HashValue) -> Result<Self, TypeError> {
+        match value {
+            HashValue::VectorUInt8(x) | HashValue::ByteArray(x) => Ok(x),
+            _ => Err(TypeError::mismatch("VECTOR_UINT8", value)),
+        }
+    }
+}
+
+impl TryFrom<&HashValue> for Vec<u8> {
+    type Error = TypeError;
+
+    fn try_from(value: &HashValue) -> Result<Self, TypeError> {
+        <&[u8]>::try_from(value).map(|x| x.to_vec())
+    }
+}
+
+impl From<String> for HashValue {
+    fn from(value: String) -> Self {
+        HashValue::String(value)
+    }
+}
+
+impl From<&str> for HashValue {
+    fn from(value: &str) -> Self {
+        HashValue::String(value.to_string())
+    }
+}
+
+impl<'a> TryFrom<&'a HashValue> for &'a str {
+    type Error = TypeError;
+
+    fn try_from(value: &'a HashValue) -> Result<Self, TypeError> {
+        match value {
+            HashValue::String(x) => Ok(x),
+            _ => Err(TypeError::mismatch("STRING", value)),
+        }
+    }
+}
+
+impl TryFrom<&HashValue> for String {
+    type Error = TypeError;
+
+    fn try_from(value: &HashValue) -> Result<Self, TypeError> {
+        <&str>::try_from(value).map(str::to_string)
+    }
+}
+
+impl From<Vec<String>> for HashValue {
+    fn from(value: Vec<String>) -> Self {
+        HashValue::VectorString(value)
+    }
+}
+
+impl From<Vec<&str>> for HashValue {
+    fn from(value: Vec<&str>) -> Self {
+        HashValue::VectorString(value.into_iter().map(str::to_string).collect())
+    }
+}
+
+impl<'a> TryFrom<&'a HashValue> for &'a [String] {
+    type Error = TypeError;
+
+    fn try_from(value: &'a HashValue) -> Result<Self, TypeError> {
+        match value {
+            HashValue::VectorString(x) => Ok(x),
+            _ => Err(TypeError::mismatch("VECTOR_STRING", value)),
+        }
+    }
+}
+
+impl TryFrom<&HashValue> for Vec<String> {
+    type Error = TypeError;
+
+    fn try_from(value: &HashValue) -> Result<Self, TypeError> {
+        <&[String]>::try_from(value).map(|x| x.to_vec())
+    }
+}
+
+impl From<Hash> for HashValue {
+    fn from(value: Hash) -> Self {
+        HashValue::Hash(value)
+    }
+}
+
+impl<'a> TryFrom<&'a HashValue> for &'a Hash {
+    type Error = TypeError;
+
+    fn try_from(value: &'a HashValue) -> Result<Self, TypeError> {
+        match value {
+            HashValue::Hash(x) => Ok(x),
+            _ => Err(TypeError::mismatch("HASH", value)),
+        }
+    }
+}
+
+impl TryFrom<&HashValue> for Hash {
+    type Error = TypeError;
+
+    fn try_from(value: &HashValue) -> Result<Self, TypeError> {
+        <&Hash>::try_from(value).cloned()
+    }
+}
+
+impl From<Vec<Hash>> for HashValue {
+    fn from(value: Vec<Hash>) -> Self {
+        HashValue::VectorHash(value)
+    }
+}
+
+impl<'a> TryFrom<&'a HashValue> for &'a [Hash] {
+    type Error = TypeError;
+
+    fn try_from(value: &'a HashValue) -> Result<Self, TypeError> {
+        match value {
+            HashValue::VectorHash(x) => Ok(x),
+            _ => Err(TypeError::mismatch("VECTOR_HASH", value)),
+        }
+    }
+}
+
+impl TryFrom<&HashValue> for Vec<Hash> {
+    type Error = TypeError;
+
+    fn try_from(value: &HashValue) -> Result<Self, TypeError> {
+        <&[Hash]>::try_from(value).map(|x| x.to_vec())
+    }
+}
+
+impl From<Schema> for HashValue {
+    fn from(value: Schema) -> Self {
+        HashValue::Schema(value)
+    }
+}
+
+impl<'a> TryFrom<&'a HashValue> for &'a Schema {
+    type Error = TypeError;
+
+    fn try_from(value: &'a HashValue) -> Result<Self, TypeError> {
+        match value {
+            HashValue::Schema(x) => Ok(x),
+            _ => Err(TypeError::mismatch("SCHEMA", value)),
+        }
+    }
+}
+
+impl TryFrom<&HashValue> for Schema {
+    type Error = TypeError;
+
+    fn try_from(value: &HashValue) -> Result<Self, TypeError> {
+        <&Schema>::try_from(value).cloned()
+    }
+}
+
+// Conversion of `HashValue::coerce`, with Karabo's `getAs` semantics:
+// - numbers, bools included, convert to any numeric type if the value is
+//   preserved exactly, otherwise `OutOfRange` or `PrecisionLoss` is returned
+// - strings are parsed, vectors from a string are comma separated
+// - vectors convert element by element
+// - anything but a hash converts to a `String`, vectors comma separated
+// - other types, and borrowed ones, have to match exactly
+pub trait Coerce<'a>: Sized {
+    fn coerce(value: &'a HashValue) -> Result<Self, TypeError>;
+}
+
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i128),
+    // kept apart from `Float` so that it is formatted as a FLOAT
+    Float32(f32),
+    Float(f64),
+}
+
+impl Number {
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(x) => x as f64,
+            Number::Float32(x) => f64::from(x),
+            Number::Float(x) => x,
+        }
+    }
+
+    fn out_of_range(&self, expected: &'static str) -> TypeError {
+        TypeError::OutOfRange {
+            value: self.to_string(),
+            expected,
+        }
+    }
+
+    fn precision_loss(&self, expected: &'static str) -> TypeError {
+        TypeError::PrecisionLoss {
+            value: self.to_string(),
+            expected,
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Number::Int(x) => write!(f, "{x}"),
+            Number::Float32(x) => write!(f, "{x}"),
+            Number::Float(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+fn number(value: &HashValue) -> Option<Number> {
+    Some(match *value {
+        HashValue::Bool(x) => Number::Int(i128::from(x)),
+        HashValue::Int8(x) => Number::Int(i128::from(x)),
+        HashValue::UInt8(x) => Number::Int(i128::from(x)),
+        HashValue::Int16(x) => Number::Int(i128::from(x)),
+        HashValue::UInt16(x) => Number::Int(i128::from(x)),
+        HashValue::Int32(x) => Number::Int(i128::from(x)),
+        HashValue::UInt32(x) => Number::Int(i128::from(x)),
+        HashValue::Int64(x) => Number::Int(i128::from(x)),
+        HashValue::UInt64(x) => Number::Int(i128::from(x)),
+        HashValue::Float32(x) => Number::Float32(x),
+        HashValue::Float64(x) => Number::Float(x),
+        _ => return None,
+    })
+}
+
+fn numbers(value: &HashValue) -> Option<Vec<Number>> {
+    fn ints<T: Copy + Into<i128>>(x: &[T]) -> Vec<Number> {
+        x.iter().map(|x| Number::Int((*x).into())).collect()
+    }
+    Some(match value {
+        HashValue::VectorBool(x) => x.iter().map(|x| Number::Int(i128::from(*x))).collect(),
+        HashValue::VectorInt8(x) => ints(x),
+        HashValue::VectorUInt8(x) | HashValue::ByteArray(x) => ints(x),
+        HashValue::VectorInt16(x) => ints(x),
+        HashValue::VectorUInt16(x) => ints(x),
+        HashValue::VectorInt32(x) => ints(x),
+        HashValue::VectorUInt32(x) => ints(x),
+        HashValue::VectorInt64(x) => ints(x),
+        HashValue::VectorUInt64(x) => ints(x),
+        HashValue::VectorFloat32(x) => x.iter().map(|x| Number::Float32(*x)).collect(),
+        HashValue::VectorFloat64(x) => x.iter().map(|x| Number::Float(*x)).collect(),
+        _ => return None,
+    })
+}
+
+trait FromNumber: Sized {
+    // Karabo name of the type, for errors
+    const NAME: &'static str;
+
+    fn from_number(number: Number) -> Result<Self, TypeError>;
+
+    fn parse(s: &str) -> Result<Self, TypeError> {
+        let s = s.trim();
+        let number = match s.parse::<i128>() {
+            Ok(x) => Number::Int(x),
+            Err(_) => match s.parse::<f64>() {
+                Ok(x) => Number::Float(x),
+                Err(_) => {
+                    return Err(TypeError::Parse {
+                        value: s.to_string(),
+                        expected: Self::NAME,
+                    })
+                }
+            },
+        };
+        Self::from_number(number)
+    }
+}
+
+macro_rules! from_number_int {
+    ($($t:ty => $name:literal),*) => {$(
+        impl FromNumber for $t {
+            const NAME: &'static str = $name;
+
+            fn from_number(number: Number) -> Result<Self, TypeError> {
+                match number {
+                    Number::Int(x) => <$t>::try_from(x).map_err(|_| number.out_of_range($name)),
+                    Number::Float32(_) | Number::Float(_) => {
+                        let x = number.as_f64();
+                        if x.is_nan() || (x.is_finite() && x.fract() != 0.0) {
+                            Err(number.precision_loss($name))
+                        } else {
+                            // saturates for infinite values, which are then out of range
+                            <$t>::try_from(x as i128).map_err(|_| number.out_of_range($name))
+                        }
+                    }
+                }
+            }
+        }
+    )*};
+}
+
+from_number_int!(
+    i8 => "INT8", u8 => "UINT8", i16 => "INT16", u16 => "UINT16",
+    i32 => "INT32", u32 => "UINT32", i64 => "INT64", u64 => "UINT64"
+);
+
+macro_rules! from_number_float {
+    ($($t:ty => $name:literal),*) => {$(
+        impl FromNumber for $t {
+            const NAME: &'static str = $name;
+
+            fn from_number(number: Number) -> Result<Self, TypeError> {
+                match number {
+                    Number::Int(x) => {
+                        let y = x as $t;
+                        if y as i128 == x {
+                            Ok(y)
+                        } else {
+                            Err(number.precision_loss($name))
+                        }
+                    }
+                    Number::Float32(_) | Number::Float(_) => {
+                        let x = number.as_f64();
+                        let y = x as $t;
+                        if x.is_finite() && y.is_infinite() {
+                            Err(number.out_of_range($name))
+                        } else if f64::from(y) == x || x.is_nan() {
+                            Ok(y)
+                        } else {
+                            Err(number.precision_loss($name))
+                        }
+                    }
+                }
+            }
+
+            // parsed as the target type, "0.1" is a FLOAT even though it
+            // is not exact
+            fn parse(s: &str) -> Result<Self, TypeError> {
+                let s = s.trim();
+                s.parse::<$t>().map_err(|_| TypeError::Parse {
+                    value: s.to_string(),
+                    expected: $name,
+                })
+            }
+        }
+    )*};
+}
+
+from_number_float!(f32 => "FLOAT", f64 => "DOUBLE");
+
+impl FromNumber for bool {
+    const NAME: &'static str = "BOOL";
+
+    fn from_number(number: Number) -> Result<Self, TypeError> {
+        Ok(match number {
+            Number::Int(x) => x != 0,
+            Number::Float32(_) | Number::Float(_) => number.as_f64() != 0.0,
+        })
+    }
+
+    fn parse(s: &str) -> Result<Self, TypeError> {
+        match s.trim() {
+            "true" | "True" => Ok(true),
+            "false" | "False" => Ok(false),
+            s => match s.parse::<f64>() {
+                Ok(x) => Self::from_number(Number::Float(x)),
+                Err(_) => Err(TypeError::Parse {
+                    value: s.to_string(),
+                    expected: Self::NAME,
+                }),
+            },
+        }
+    }
+}
+
+fn coerce_scalar<T: FromNumber>(value: &HashValue) -> Result<T, TypeError> {
+    match value {
+        HashValue::String(x) => T::parse(x),
+        _ => match number(value) {
+            Some(number) => T::from_number(number),
+            None => Err(TypeError::mismatch(T::NAME, value)),
+        },
+    }
+}
+
+fn coerce_vector<T: FromNumber>(
+    value: &HashValue,
+    name: &'static str,
+) -> Result<Vec<T>, TypeError> {
+    match value {
+        HashValue::String(x) if x.trim().is_empty() => Ok(Vec::new()),
+        HashValue::String(x) => x.split(',').map(T::parse).collect(),
+        HashValue::VectorString(x) => x.iter().map(|x| T::parse(x)).collect(),
+        _ => match numbers(value) {
+            Some(numbers) => numbers.into_iter().map(T::from_number).collect(),
+            None => Err(TypeError::mismatch(name, value)),
+        },
+    }
+}
+
+macro_rules! coerce_number {
+    ($($t:ty => $vector:literal),*) => {$(
+        impl Coerce<'_> for $t {
+            fn coerce(value: &HashValue) -> Result<Self, TypeError> {
+                coerce_scalar(value)
+            }
+        }
+
+        impl Coerce<'_> for Vec<$t> {
+            fn coerce(value: &HashValue) -> Result<Self, TypeError> {
+                coerce_vector(value, $vector)
+            }
+        }
+    )*};
+}
+
+coerce_number!(
+    bool => "VECTOR_BOOL", i8 => "VECTOR_INT8", u8 => "VECTOR_UINT8",
+    i16 => "VECTOR_INT16", u16 => "VECTOR_UINT16", i32 => "VECTOR_INT32",
+    u32 => "VECTOR_UINT32", i64 => "VECTOR_INT64", u64 => "VECTOR_UINT64",
+    f32 => "VECTOR_FLOAT", f64 => "VECTOR_DOUBLE"
+);
+
+fn join<T: ToString>(x: &[T]) -> String {
+    x.iter().map(T::to_string).collect::<Vec<_>>().join(",")
+}
+
+impl Coerce<'_> for String {
+    fn coerce(value: &HashValue) -> Result<Self, TypeError> {
+        Ok(match value {
+            HashValue::Bool(x) => x.to_string(),
+            HashValue::Char(x) => x.to_string(),
+            HashValue::String(x) => x.clone(),
+            HashValue::ComplexFloat32(x) => x.to_string(),
+            HashValue::ComplexFloat64(x) => x.to_string(),
+            HashValue::VectorBool(x) => join(x),
+            HashValue::VectorChar(x) => join(x),
+            HashValue::VectorString(x) => x.join(","),
+            HashValue::VectorComplexFloat32(x) => join(x),
+            HashValue::VectorComplexFloat64(x) => join(x),
+            _ => match (number(value), numbers(value)) {
+                (Some(number), _) => number.to_string(),
+                (_, Some(numbers)) => join(&numbers),
+                _ => return Err(TypeError::mismatch("STRING", value)),
+            },
+        })
+    }
+}
+
+impl Coerce<'_> for Vec<String> {
+    fn coerce(value: &HashValue) -> Result<Self, TypeError> {
+        fn strings<T: ToString>(x: &[T]) -> Vec<String> {
+            x.iter().map(T::to_string).collect()
+        }
+        Ok(match value {
+            HashValue::String(x) if x.is_empty() => Vec::new(),
+            HashValue::String(x) => x.split(',').map(str::to_string).collect(),
+            HashValue::VectorString(x) => x.clone(),
+            HashValue::VectorBool(x) => strings(x),
+            HashValue::VectorChar(x) => strings(x),
+            HashValue::VectorComplexFloat32(x) => strings(x),
+            HashValue::VectorComplexFloat64(x) => strings(x),
+            _ => match numbers(value) {
+                Some(numbers) => strings(&numbers),
+                None => return Err(TypeError::mismatch("VECTOR_STRING", value)),
+            },
+        })
+    }
+}
+
+macro_rules! coerce_exact {
+    ($($t:ty),* $(,)?) => {$(
+        impl<'a> Coerce<'a> for $t {
+            fn coerce(value: &'a HashValue) -> Result<Self, TypeError> {
+                Self::try_from(value)
+            }
+        }
+    )*};
+}
+
+coerce_exact!(
+    char,
+    Vec<char>,
+    Complex<f32>,
+    Vec<Complex<f32>>,
+    Complex<f64>,
+    Vec<Complex<f64>>,
+    Hash,
+    Vec<Hash>,
+    Schema,
+    &'a str,
+    &'a Hash,
+    &'a Schema,
+    &'a [bool],
+    &'a [char],
+    &'a [i8],
+    &'a [u8],
+    &'a [i16],
+    &'a [u16],
+    &'a [i32],
+    &'a [u32],
+    &'a [i64],
+    &'a [u64],
+    &'a [f32],
+    &'a [f64],
+    &'a [Complex<f32>],
+    &'a [Complex<f64>],
+    &'a [String],
+    &'a [Hash],
+);
@@ -0,0 +1,103 @@
+use crate::binary_readers::{read_hash_with, DecodeError, DecodeOptions};
+use crate::hash::Hash;
+
+// Splits a byte stream into `[u32 len][hash]` frames.
+// Chunks can be pushed as they arrive, a frame can span several chunks
+// and a chunk can contain several frames.
+#[derive(Clone, Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    // start of the first frame not yet decoded
+    start: usize,
+    // set once the stream cannot be resynchronized
+    failed: Option<DecodeError>,
+    opts: DecodeOptions,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        FrameDecoder::with_options(DecodeOptions::default())
+    }
+
+    pub fn with_options(opts: DecodeOptions) -> Self {
+        FrameDecoder {
+            buffer: Vec::new(),
+            start: 0,
+            failed: None,
+            opts,
+        }
+    }
+
+    // chunks pushed after a failure are dropped until `reset`
+    pub fn push(&mut self, chunk: &[u8]) {
+        if self.failed.is_some() {
+            return;
+        }
+        if self.start > 0 {
+            self.buffer.drain(..self.start);
+            self.start = 0;
+        }
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    // number of bytes received and not yet decoded
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.start
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.failed.is_some()
+    }
+
+    // drops the buffered data and any failure, the next chunk has to start
+    // at a frame boundary
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.start = 0;
+        self.failed = None;
+    }
+
+    // Returns the next complete hash, `Ok(None)` if more data is needed.
+    // A frame that fails to decode is dropped, the following frames are
+    // still available. A length that is too large cannot be skipped, the
+    // same error is returned until `reset` is called.
+    pub fn next_frame(&mut self) -> Result<Option<Hash>, DecodeError> {
+        if let Some(err) = &self.failed {
+            return Err(err.clone());
+        }
+        let data = &self.buffer[self.start..];
+        if data.len() < 4 {
+            return Ok(None);
+        }
+        let length = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let size = match usize::try_from(length) {
+            Ok(size) if size <= self.opts.max_total_bytes => size,
+            _ => {
+                // the stream cannot be resynchronized
+                let err = DecodeError::OversizedLength {
+                    length: u64::from(length),
+                    offset: 0,
+                    path: String::new(),
+                };
+                self.buffer.clear();
+                self.start = 0;
+                self.failed = Some(err.clone());
+                return Err(err);
+            }
+        };
+        if data.len() - 4 < size {
+            return Ok(None);
+        }
+        let mut frame = &data[4..4 + size];
+        self.start += 4 + size;
+        let hash = read_hash_with(&mut frame, &self.opts)?;
+        if !frame.is_empty() {
+            return Err(DecodeError::LengthMismatch {
+                length: u64::from(length),
+                offset: size - frame.len(),
+                path: String::new(),
+            });
+        }
+        Ok(Some(hash))
+    }
+}
@@ -1,10 +1,149 @@
 use crate::attributes::Attributes;
-use crate::types::{get_hashtype, HashValue};
+use crate::convert::Coerce;
+use crate::types::{HashValue, TypeError};
 use std::collections::HashMap;
 use std::fmt;
 use std::ops::{Deref, Index};
 use wasm_bindgen::prelude::wasm_bindgen;
 
+/// Builds a `Hash`, values are converted with `From` and keys are paths as
+/// in `Hash::insert`:
+///
+/// ```ignore
+/// hash! {
+///     "a" => 1i32,
+///     "b" => { "c" => "text" @ { "unit" => "m" } },
+///     "v" => vec![1.0f64, 2.0],
+///     "rows" => [{ "x" => 1u8 }, { "x" => 2u8 }],
+/// }
+/// ```
+///
+/// `{ .. }` is a nested hash, `[{ .. }, ..]` a vector of hashes and
+/// `@ { .. }` the attributes of the node.
+///
+/// Panics if `Hash::insert` refuses a key, e.g. `"rows[1].x"` without a
+/// row 0.
+#[macro_export]
+macro_rules! hash {
+    () => {
+        $crate::hash::Hash::new()
+    };
+    (@entries $hash:ident;) => {};
+    (@entries $hash:ident; $key:expr => $($rest:tt)*) => {
+        $crate::hash!(@value $hash; $key; []; $($rest)*)
+    };
+    // collects the tokens of the value up to `@`, `,` or the end
+    (@value $hash:ident; $key:expr; [$($value:tt)*]; @ { $($attrs:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::hash!(
+            @insert $hash;
+            $key;
+            $crate::hash!(@into $($value)*);
+            $crate::hash!(@attrs $($attrs)*)
+        );
+        $crate::hash!(@entries $hash; $($($rest)*)?)
+    };
+    (@value $hash:ident; $key:expr; [$($value:tt)*]; , $($rest:tt)*) => {
+        $crate::hash!(
+            @insert $hash;
+            $key;
+            $crate::hash!(@into $($value)*);
+            $crate::attributes::Attributes::new()
+        );
+        $crate::hash!(@entries $hash; $($rest)*)
+    };
+    (@value $hash:ident; $key:expr; [$($value:tt)*];) => {
+        $crate::hash!(@value $hash; $key; [$($value)*]; ,)
+    };
+    (@value $hash:ident; $key:expr; [$($value:tt)*]; $next:tt $($rest:tt)*) => {
+        $crate::hash!(@value $hash; $key; [$($value)* $next]; $($rest)*)
+    };
+    (@insert $hash:ident; $key:expr; $value:expr; $attrs:expr) => {{
+        let key: &str = $key;
+        if !$hash.insert_attrs(key, $value, $attrs) {
+            panic!("hash!: invalid path {}", key);
+        }
+    }};
+    (@into { $($inner:tt)* }) => {
+        $crate::types::HashValue::Hash($crate::hash!($($inner)*))
+    };
+    (@into [$({ $($row:tt)* }),+ $(,)?]) => {
+        $crate::types::HashValue::VectorHash(vec![$($crate::hash!($($row)*)),+])
+    };
+    (@into $($value:tt)+) => {
+        $crate::types::HashValue::from($($value)+)
+    };
+    (@attrs $($key:expr => $value:expr),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut attrs = $crate::attributes::Attributes::new();
+        $(attrs.insert($key, $crate::types::HashValue::from($value));)*
+        attrs
+    }};
+    ($($body:tt)+) => {{
+        let mut hash = $crate::hash::Hash::new();
+        $crate::hash!(@entries hash; $($body)+);
+        hash
+    }};
+}
+
+// Why a path could not be resolved
+enum PathError<'p> {
+    MissingKey(&'p str),
+    OutOfRange {
+        key: &'p str,
+        index: usize,
+        len: usize,
+    },
+}
+
+pub(crate) fn values_equal(a: &HashValue, b: &HashValue, ordered: bool, attrs: bool) -> bool {
+    match (a, b) {
+        (HashValue::Hash(a), HashValue::Hash(b)) => a.equals(b, ordered, attrs),
+        (HashValue::VectorHash(a), HashValue::VectorHash(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.equals(b, ordered, attrs))
+        }
+        (HashValue::Schema(a), HashValue::Schema(b)) => {
+            a.class_id == b.class_id && a.hash.equals(&b.hash, ordered, attrs)
+        }
+        (a, b) => a == b,
+    }
+}
+
+// Splits a path segment like `rows[2]` into its key and row index
+fn parse_segment(segment: &str) -> (&str, Option<usize>) {
+    if let Some(head) = segment.strip_suffix(']') {
+        if let Some((key, index)) = head.split_once('[') {
+            if let Ok(index) = index.parse() {
+                return (key, Some(index));
+            }
+        }
+    }
+    (segment, None)
+}
+
+// What happens to the attributes of existing nodes in `Hash::merge`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergePolicy {
+    // the attributes are replaced by the ones of the merged node
+    #[default]
+    ReplaceAttributes,
+    // the attributes of the merged node are added, existing ones are kept
+    // unless overwritten
+    MergeAttributes,
+}
+
+// Paths below `key` in `paths`, `None` if `key` itself is selected
+fn sub_paths<'p>(paths: &[&'p str], key: &str) -> Option<Vec<&'p str>> {
+    let mut sub = Vec::new();
+    for path in paths {
+        match path.strip_prefix(key) {
+            Some("") => return None,
+            Some(rest) if rest.starts_with('.') || rest.starts_with('[') => sub.push(rest),
+            _ => {}
+        }
+    }
+    Some(sub)
+}
+
 #[derive(Clone, Debug)]
 pub struct Node {
     pub key: String,
@@ -37,88 +176,366 @@ impl Hash {
         if self.store.is_empty() {
             return Vec::new();
         }
-        return self
-            .store
+        self.store
             .iter()
             .map(|x| x.key.clone())
-            .collect::<Vec<String>>();
-    }
-
-    pub(crate) fn get_index(&self, index: usize) -> Option<&Node> {
-        self.store.get(index)
+            .collect::<Vec<String>>()
     }
 
     pub fn len(&self) -> usize {
         self.store.len()
     }
 
+    pub fn iter(&self) -> HashIterator<'_> {
+        HashIterator {
+            nodes: self.store.iter(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> HashIteratorMut<'_> {
+        HashIteratorMut {
+            nodes: self.store.iter_mut(),
+        }
+    }
+
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &HashValue> {
+        self.store.iter().map(|node| &node.value)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.store.is_empty()
     }
 
-    pub fn get(&self, key: &str) -> Option<&HashValue> {
-        if self.store.is_empty() {
-            return None;
+    fn resolve<'p>(&self, path: &'p str) -> Result<&Node, PathError<'p>> {
+        let (segment, rest) = match path.split_once('.') {
+            Some((segment, rest)) => (segment, Some(rest)),
+            None => (path, None),
+        };
+        let (key, index) = parse_segment(segment);
+        let node = match self.key_map.get(key) {
+            Some(idx) => &self.store[*idx],
+            None => return Err(PathError::MissingKey(key)),
+        };
+        match (index, rest, &node.value) {
+            (None, None, _) => Ok(node),
+            (None, Some(rest), HashValue::Hash(hash)) => hash.resolve(rest),
+            (Some(index), _, HashValue::VectorHash(rows)) if index >= rows.len() => {
+                Err(PathError::OutOfRange {
+                    key,
+                    index,
+                    len: rows.len(),
+                })
+            }
+            (Some(index), Some(rest), HashValue::VectorHash(rows)) => rows[index].resolve(rest),
+            // a row is not a node, see `get_hash`
+            _ => Err(PathError::MissingKey(segment)),
         }
-        let parts: Vec<&str> = key.split('.').collect();
-        let position = self.key_map.get(parts[0]);
-        match position {
-            Some(idx) => match &self.store[*idx].value {
-                HashValue::Hash(x) if parts.len() > 1 => {
-                    let key = parts[1..].join(".");
-                    x.get(&key)
+    }
+
+    fn get_node(&self, path: &str) -> Option<&Node> {
+        self.resolve(path).ok()
+    }
+
+    // Node with the literal `key`, no path resolution. Needed for keys
+    // containing `.` or `[`, e.g. the ones of a `flatten`ed hash.
+    pub fn get_key(&self, key: &str) -> Option<&Node> {
+        self.key_map.get(key).map(|idx| &self.store[*idx])
+    }
+
+    pub fn get_key_mut(&mut self, key: &str) -> Option<&mut Node> {
+        self.key_map.get(key).map(|idx| &mut self.store[*idx])
+    }
+
+    fn get_node_mut(&mut self, path: &str) -> Option<&mut Node> {
+        let (segment, rest) = match path.split_once('.') {
+            Some((segment, rest)) => (segment, Some(rest)),
+            None => (path, None),
+        };
+        let (key, index) = parse_segment(segment);
+        let node = &mut self.store[*self.key_map.get(key)?];
+        if index.is_none() && rest.is_none() {
+            return Some(node);
+        }
+        match (index, rest, &mut node.value) {
+            (None, Some(rest), HashValue::Hash(hash)) => hash.get_node_mut(rest),
+            (Some(index), Some(rest), HashValue::VectorHash(rows)) => {
+                rows.get_mut(index)?.get_node_mut(rest)
+            }
+            _ => None,
+        }
+    }
+
+    // Returns the hash at `path`, which can also be a row of a vector of
+    // hashes, e.g. `rows[2]`
+    pub fn get_hash(&self, path: &str) -> Option<&Hash> {
+        let (parent, last) = match path.rsplit_once('.') {
+            Some((parent, last)) => (Some(parent), last),
+            None => (None, path),
+        };
+        match parse_segment(last) {
+            (key, Some(index)) => {
+                let path = match parent {
+                    Some(parent) => format!("{parent}.{key}"),
+                    None => key.to_string(),
+                };
+                match self.get(&path)? {
+                    HashValue::VectorHash(rows) => rows.get(index),
+                    _ => None,
                 }
-                _x => Some(_x),
-            },
-            None => None,
+            }
+            (_, None) => self.get(path)?.as_hash(),
         }
     }
 
-    pub fn get_mut(&mut self, key: &str) -> Option<&mut HashValue> {
-        if self.store.is_empty() {
-            return None;
+    pub fn get_hash_mut(&mut self, path: &str) -> Option<&mut Hash> {
+        let (parent, last) = match path.rsplit_once('.') {
+            Some((parent, last)) => (Some(parent), last),
+            None => (None, path),
+        };
+        let (key, index) = parse_segment(last);
+        let path = match (parent, index) {
+            (Some(parent), Some(_)) => format!("{parent}.{key}"),
+            (None, Some(_)) => key.to_string(),
+            (_, None) => path.to_string(),
+        };
+        match (self.get_mut(&path)?, index) {
+            (HashValue::VectorHash(rows), Some(index)) => rows.get_mut(index),
+            (HashValue::Hash(hash), None) => Some(hash),
+            _ => None,
         }
-        let position = self.key_map.get(key);
-        match position {
-            Some(idx) => Some(&mut self.store[*idx].value),
-            None => None,
+    }
+
+    // Value of the node at `path`. Rows of vectors of hashes are not nodes,
+    // `get("rows[0]")` is `None`, they are reached with `get_hash`.
+    pub fn get(&self, path: &str) -> Option<&HashValue> {
+        self.get_node(path).map(|node| &node.value)
+    }
+
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut HashValue> {
+        self.get_node_mut(path).map(|node| &mut node.value)
+    }
+
+    pub fn get_attributes(&self, path: &str) -> Option<&Attributes> {
+        self.get_node(path).map(|node| &node.attrs)
+    }
+
+    pub fn get_attributes_mut(&mut self, path: &str) -> Option<&mut Attributes> {
+        self.get_node_mut(path).map(|node| &mut node.attrs)
+    }
+
+    pub fn get_attribute(&self, path: &str, attr: &str) -> Option<&HashValue> {
+        self.get_attributes(path)?.get(attr)
+    }
+
+    // Returns false if there is no node at `path`
+    pub fn set_attribute(&mut self, path: &str, attr: &str, value: HashValue) -> bool {
+        match self.get_attributes_mut(path) {
+            Some(attrs) => {
+                attrs.insert(attr, value);
+                true
+            }
+            None => false,
         }
     }
 
-    pub fn get_attributes(&self, key: &str) -> Option<&Attributes> {
-        if self.store.is_empty() {
-            return None;
+    pub fn remove_attribute(&mut self, path: &str, attr: &str) -> Option<HashValue> {
+        self.get_attributes_mut(path)?
+            .remove(attr)
+            .map(|attr| attr.value)
+    }
+
+    // Removes the node at `path`, the order of the remaining nodes is kept.
+    // Rows of vectors of hashes are not nodes, `rows[2]` removes nothing.
+    pub fn remove(&mut self, path: &str) -> Option<Node> {
+        match path.rsplit_once('.') {
+            Some((parent, key)) => self.get_hash_mut(parent)?.remove_key(key),
+            None => self.remove_key(path),
         }
-        for node in self.store.iter() {
-            if node.key == key {
-                return Some(&node.attrs);
-            }
+    }
+
+    // Keeps the top level nodes for which `f` returns true
+    pub fn retain<F: FnMut(&Node) -> bool>(&mut self, f: F) {
+        self.store.retain(f);
+        self.reindex(0);
+    }
+
+    pub fn clear(&mut self) {
+        self.store.clear();
+        self.key_map.clear();
+    }
+
+    // Removes the node with the literal `key`, see `get_key`
+    pub fn remove_key(&mut self, key: &str) -> Option<Node> {
+        let idx = self.key_map.remove(key)?;
+        let node = self.store.remove(idx);
+        self.reindex(idx);
+        Some(node)
+    }
+
+    // rebuilds the positions of the nodes from `start` on
+    fn reindex(&mut self, start: usize) {
+        if start == 0 {
+            self.key_map.clear();
+        }
+        for (idx, node) in self.store.iter().enumerate().skip(start) {
+            self.key_map.insert(node.key.clone(), idx);
         }
-        None
     }
 
-    pub fn get_mut_attributes(&mut self, key: &str) -> Option<&mut HashValue> {
-        if self.store.is_empty() {
-            return None;
+    // Value at `path` converted to `T`, which has to match its type exactly,
+    // e.g. `hash.get_as::<&str>("state")`
+    pub fn get_as<'a, T>(&'a self, path: &str) -> Result<T, TypeError>
+    where
+        T: TryFrom<&'a HashValue, Error = TypeError>,
+    {
+        match self.get(path) {
+            Some(value) => T::try_from(value),
+            None => Err(TypeError::MissingPath {
+                path: path.to_string(),
+            }),
         }
+    }
 
-        for attr in self.store.iter_mut() {
-            if attr.key == key {
-                return Some(&mut attr.value);
+    // Value at `path` converted to `T` with `HashValue::coerce`, e.g.
+    // `hash.get_coerced::<f64>("motor.position")` for any numeric property
+    pub fn get_coerced<'a, T: Coerce<'a>>(&'a self, path: &str) -> Result<T, TypeError> {
+        match self.get(path) {
+            Some(value) => value.coerce(),
+            None => Err(TypeError::MissingPath {
+                path: path.to_string(),
+            }),
+        }
+    }
+
+    // Sets the value at `path` like `insert`, the attributes of an existing
+    // node are kept. Returns false if `insert` refuses the path.
+    #[must_use]
+    pub fn set<T: Into<HashValue>>(&mut self, path: &str, value: T) -> bool {
+        match self.get_mut(path) {
+            Some(current) => {
+                *current = value.into();
+                true
             }
+            None => self.insert(path, value.into()),
         }
-        None
     }
 
     #[inline]
-    pub fn insert(&mut self, key: &str, value: HashValue) {
+    #[must_use]
+    pub fn insert(&mut self, path: &str, value: HashValue) -> bool {
         let attrs = Attributes::new();
-        self.insert_index_attrs(key, value, attrs);
+        self.insert_attrs(path, value, attrs)
     }
 
-    #[inline]
-    pub fn insert_attrs(&mut self, key: &str, value: HashValue, attrs: Attributes) {
-        self.insert_index_attrs(key, value, attrs);
+    // Follows the dotted `path`, missing intermediate nodes are created and
+    // values that are not hashes on the way are replaced by empty hashes.
+    // Rows of vectors of hashes are addressed as `rows[2].x`, the index can
+    // be at most the length of the vector, which appends a row.
+    //
+    // A path ending with an index, e.g. `rows[2]`, replaces the whole row and
+    // needs a `HashValue::Hash`.
+    //
+    // Returns false and leaves the hash unchanged if an index is past the
+    // end of its vector or a row would be replaced by something else than a
+    // hash.
+    #[must_use]
+    pub fn insert_attrs(&mut self, path: &str, value: HashValue, attrs: Attributes) -> bool {
+        if !self.can_insert(path, &value) {
+            return false;
+        }
+        self.insert_path(path, value, attrs);
+        true
+    }
+
+    fn can_insert(&self, path: &str, value: &HashValue) -> bool {
+        let (segment, rest) = match path.split_once('.') {
+            Some((segment, rest)) => (segment, Some(rest)),
+            None => (path, None),
+        };
+        let (key, index) = parse_segment(segment);
+        let current = self.get_key(key).map(|node| &node.value);
+        let empty = Hash::new();
+        match (index, rest) {
+            (None, None) => true,
+            (None, Some(rest)) => match current {
+                Some(HashValue::Hash(hash)) => hash.can_insert(rest, value),
+                _ => empty.can_insert(rest, value),
+            },
+            (Some(index), rest) => {
+                let rows = match current {
+                    Some(HashValue::VectorHash(rows)) => rows.as_slice(),
+                    _ => &[],
+                };
+                match rest {
+                    _ if index > rows.len() => false,
+                    None => matches!(value, HashValue::Hash(_)),
+                    Some(rest) => rows.get(index).unwrap_or(&empty).can_insert(rest, value),
+                }
+            }
+        }
+    }
+
+    // `insert_attrs` once `can_insert` accepted the path
+    fn insert_path(&mut self, path: &str, value: HashValue, attrs: Attributes) {
+        let (segment, rest) = match path.split_once('.') {
+            Some((segment, rest)) => (segment, Some(rest)),
+            None => (path, None),
+        };
+        match (parse_segment(segment), rest) {
+            ((key, None), Some(rest)) => {
+                self.get_or_insert_hash(key).insert_path(rest, value, attrs)
+            }
+            ((key, Some(index)), Some(rest)) => self
+                .get_or_insert_row(key, index)
+                .insert_path(rest, value, attrs),
+            ((key, Some(index)), None) => {
+                if let HashValue::Hash(hash) = value {
+                    *self.get_or_insert_row(key, index) = hash;
+                }
+            }
+            ((_, None), None) => self.insert_index_attrs(path, value, attrs),
+        }
+    }
+
+    fn get_or_insert_row(&mut self, key: &str, index: usize) -> &mut Hash {
+        let idx = match self.key_map.get(key) {
+            Some(idx) => *idx,
+            None => {
+                self.insert_index_attrs(key, HashValue::VectorHash(Vec::new()), Attributes::new());
+                self.store.len() - 1
+            }
+        };
+        let node = &mut self.store[idx];
+        if !matches!(node.value, HashValue::VectorHash(_)) {
+            node.value = HashValue::VectorHash(Vec::new());
+        }
+        match &mut node.value {
+            HashValue::VectorHash(rows) => {
+                if rows.len() <= index {
+                    rows.resize(index + 1, Hash::new());
+                }
+                &mut rows[index]
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn get_or_insert_hash(&mut self, key: &str) -> &mut Hash {
+        let idx = match self.key_map.get(key) {
+            Some(idx) => *idx,
+            None => {
+                self.insert_index_attrs(key, HashValue::Hash(Hash::new()), Attributes::new());
+                self.store.len() - 1
+            }
+        };
+        let node = &mut self.store[idx];
+        if !matches!(node.value, HashValue::Hash(_)) {
+            node.value = HashValue::Hash(Hash::new());
+        }
+        match &mut node.value {
+            HashValue::Hash(hash) => hash,
+            _ => unreachable!(),
+        }
     }
 
     pub(crate) fn insert_index_attrs(&mut self, key: &str, value: HashValue, attrs: Attributes) {
@@ -135,57 +552,259 @@ impl Hash {
             }
         }
     }
-}
 
-impl PartialEq for Hash {
-    fn eq(&self, other: &Self) -> bool {
+    // Merges `other` into this hash like Karabo's `Hash.merge`. Missing nodes
+    // are created, nested hashes are merged recursively and other values are
+    // replaced, vectors of hashes included.
+    pub fn merge(&mut self, other: &Hash, policy: MergePolicy) {
+        self.merge_nodes(other, policy, None);
+    }
+
+    // Same as `merge`, only the nodes at or below the `paths` of `other` are
+    // merged. Rows are selected as `rows[1]` or `rows[1].x`, they are merged
+    // into the row with the same index, which is created if needed.
+    pub fn merge_selected(&mut self, other: &Hash, policy: MergePolicy, paths: &[&str]) {
+        self.merge_nodes(other, policy, Some(paths));
+    }
+
+    fn merge_nodes(&mut self, other: &Hash, policy: MergePolicy, selected: Option<&[&str]>) {
+        for node in &other.store {
+            let selection = match selected {
+                Some(paths) => match sub_paths(paths, &node.key) {
+                    Some(sub) if sub.is_empty() => continue,
+                    sub => sub,
+                },
+                None => None,
+            };
+            match (&node.value, selection) {
+                (HashValue::Hash(hash), None) => self
+                    .get_or_insert_hash(&node.key)
+                    .merge_nodes(hash, policy, None),
+                (HashValue::Hash(hash), Some(paths)) => {
+                    let paths: Vec<&str> =
+                        paths.iter().filter_map(|p| p.strip_prefix('.')).collect();
+                    if paths.is_empty() {
+                        continue;
+                    }
+                    self.get_or_insert_hash(&node.key)
+                        .merge_nodes(hash, policy, Some(&paths));
+                }
+                (HashValue::VectorHash(rows), Some(paths)) => {
+                    for path in paths {
+                        let (head, rest) = match path.split_once('.') {
+                            Some((head, rest)) => (head, Some(rest)),
+                            None => (path, None),
+                        };
+                        let (index, row) = match parse_segment(head) {
+                            ("", Some(index)) => match rows.get(index) {
+                                Some(row) => (index, row),
+                                None => continue,
+                            },
+                            _ => continue,
+                        };
+                        let rest = rest.as_ref().map(std::slice::from_ref);
+                        self.get_or_insert_row(&node.key, index)
+                            .merge_nodes(row, policy, rest);
+                    }
+                }
+                // leaves can't have selected paths below them
+                (_, Some(_)) => continue,
+                (value, None) => match self.key_map.get(&node.key) {
+                    Some(idx) => self.store[*idx].value = value.clone(),
+                    None => self.insert_index_attrs(&node.key, value.clone(), Attributes::new()),
+                },
+            }
+            if let Some(idx) = self.key_map.get(&node.key) {
+                let attrs = &mut self.store[*idx].attrs;
+                match policy {
+                    MergePolicy::ReplaceAttributes => *attrs = node.attrs.clone(),
+                    MergePolicy::MergeAttributes => {
+                        for (key, value) in &node.attrs {
+                            attrs.insert(key, value.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Like Karabo's `fullyEquals`: same keys in the same order with the same
+    // values and types, and fully equal attributes. Applies to nested hashes,
+    // rows of vectors of hashes and schemas as well.
+    pub fn fully_equals(&self, other: &Hash) -> bool {
+        self.equals(other, true, true)
+    }
+
+    // Same nodes and attributes regardless of their order, at any depth
+    pub fn equals_ignoring_order(&self, other: &Hash) -> bool {
+        self.equals(other, false, true)
+    }
+
+    // Same keys in the same order with the same values, attributes are not
+    // compared
+    pub fn equals_ignoring_attributes(&self, other: &Hash) -> bool {
+        self.equals(other, true, false)
+    }
+
+    fn equals(&self, other: &Hash, ordered: bool, attrs: bool) -> bool {
         if self.len() != other.len() {
             return false;
         }
-        let other_keys = other.keys();
-        if self
-            .keys()
-            .iter()
-            .zip(&other_keys)
-            .filter(|&(a, b)| {
-                a != b
-                    && get_hashtype(self.get(a).expect("")) != get_hashtype(other.get(a).expect(""))
-            })
-            .count()
-            != 0
-        {
-            return false;
+        self.store.iter().enumerate().all(|(idx, node)| {
+            let other_node = if ordered {
+                &other.store[idx]
+            } else {
+                match other.get_key(&node.key) {
+                    Some(other_node) => other_node,
+                    None => return false,
+                }
+            };
+            let attrs_equal = if !attrs {
+                true
+            } else if ordered {
+                node.attrs.fully_equals(&other_node.attrs)
+            } else {
+                node.attrs.equals_ignoring_order(&other_node.attrs)
+            };
+            node.key == other_node.key
+                && attrs_equal
+                && values_equal(&node.value, &other_node.value, ordered, attrs)
+        })
+    }
+
+    // Full paths of all leaves, in insertion order. Nested hashes are
+    // descended into, empty ones are leaves themselves.
+    pub fn paths(&self) -> Vec<String> {
+        self.paths_with(false)
+    }
+
+    // Same as `paths`, with `into_vectors` the rows of vectors of hashes are
+    // descended into as well, e.g. `rows[0].x`
+    pub fn paths_with(&self, into_vectors: bool) -> Vec<String> {
+        let mut paths = Vec::new();
+        self.collect_paths("", into_vectors, &mut paths);
+        paths
+    }
+
+    fn collect_paths(&self, prefix: &str, into_vectors: bool, paths: &mut Vec<String>) {
+        for node in &self.store {
+            let path = format!("{prefix}{}", node.key);
+            match &node.value {
+                HashValue::Hash(hash) if !hash.is_empty() => {
+                    hash.collect_paths(&format!("{path}."), into_vectors, paths)
+                }
+                HashValue::VectorHash(rows) if into_vectors && !rows.is_empty() => {
+                    for (index, row) in rows.iter().enumerate() {
+                        if row.is_empty() {
+                            paths.push(format!("{path}[{index}]"));
+                        } else {
+                            row.collect_paths(&format!("{path}[{index}]."), into_vectors, paths);
+                        }
+                    }
+                }
+                _ => paths.push(path),
+            }
         }
-        for key in self.keys() {
-            if self.get(&key) != other.get(&key) {
-                return false;
+    }
+
+    // Single level hash keyed by the full path of every leaf, see `paths`.
+    // Leaves keep their attributes, the attributes of nested hashes are lost.
+    // The keys are literal, they are read with `get_key`, not `get`.
+    pub fn flatten(&self) -> Hash {
+        let mut flat = Hash::new();
+        self.flatten_into("", &mut flat);
+        flat
+    }
+
+    fn flatten_into(&self, prefix: &str, flat: &mut Hash) {
+        for node in &self.store {
+            let path = format!("{prefix}{}", node.key);
+            match &node.value {
+                HashValue::Hash(hash) if !hash.is_empty() => {
+                    hash.flatten_into(&format!("{path}."), flat)
+                }
+                value => flat.insert_index_attrs(&path, value.clone(), node.attrs.clone()),
             }
-            if self.get_attributes(&key) != other.get_attributes(&key) {
-                return false;
+        }
+    }
+
+    // Reverse of `flatten`, the keys are inserted as paths. Returns `None` if
+    // `insert` refuses one of them.
+    pub fn unflatten(&self) -> Option<Hash> {
+        let mut hash = Hash::new();
+        for node in &self.store {
+            if !hash.insert_attrs(&node.key, node.value.clone(), node.attrs.clone()) {
+                return None;
             }
         }
-        true
+        Some(hash)
+    }
+}
+
+// Same as `equals_ignoring_order`
+impl PartialEq for Hash {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals_ignoring_order(other)
     }
 }
 
+// Borrowing iterator over the nodes of a hash, in insertion order
 pub struct HashIterator<'a> {
-    hash: &'a Hash,
-    index: usize,
+    nodes: std::slice::Iter<'a, Node>,
 }
 
 impl<'a> Iterator for HashIterator<'a> {
-    type Item = Node;
+    type Item = (&'a str, &'a HashValue, &'a Attributes);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let result = self.hash.get_index(self.index);
-        self.index += 1;
-        match result {
-            Some(x) => {
-                self.index += 1;
-                Some(x.clone())
-            }
-            None => None,
-        }
+        self.nodes
+            .next()
+            .map(|node| (node.key.as_str(), &node.value, &node.attrs))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.nodes.size_hint()
+    }
+}
+
+impl ExactSizeIterator for HashIterator<'_> {}
+
+// Keys can't be changed, they are indexed
+pub struct HashIteratorMut<'a> {
+    nodes: std::slice::IterMut<'a, Node>,
+}
+
+impl<'a> Iterator for HashIteratorMut<'a> {
+    type Item = (&'a str, &'a mut HashValue, &'a mut Attributes);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nodes
+            .next()
+            .map(|node| (node.key.as_str(), &mut node.value, &mut node.attrs))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.nodes.size_hint()
+    }
+}
+
+impl ExactSizeIterator for HashIteratorMut<'_> {}
+
+impl<'a> IntoIterator for &'a Hash {
+    type Item = (&'a str, &'a HashValue, &'a Attributes);
+    type IntoIter = HashIterator<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Hash {
+    type Item = (&'a str, &'a mut HashValue, &'a mut Attributes);
+    type IntoIter = HashIteratorMut<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
     }
 }
 
@@ -198,13 +817,21 @@ impl IntoIterator for Hash {
     }
 }
 
-impl<'a> Index<&'a str> for Hash {
+impl Index<&str> for Hash {
     type Output = HashValue;
 
     fn index(&self, index: &str) -> &HashValue {
-        match self.get(index) {
-            Some(value) => value,
-            None => panic!("Missing Key {}", index),
+        match self.resolve(index) {
+            Ok(node) => &node.value,
+            Err(PathError::MissingKey(key)) => panic!("Missing Key {} in {}", key, index),
+            Err(PathError::OutOfRange {
+                key,
+                index: row,
+                len,
+            }) => panic!(
+                "Index {} out of range for {} of length {} in {}",
+                row, key, len, index
+            ),
         }
     }
 }
@@ -217,7 +844,7 @@ impl Index<String> for Hash {
     }
 }
 
-impl<'a> Index<&'a String> for Hash {
+impl Index<&String> for Hash {
     type Output = HashValue;
 
     fn index(&self, index: &String) -> &HashValue {
@@ -228,16 +855,8 @@ impl<'a> Index<&'a String> for Hash {
 impl fmt::Display for Hash {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let ret: Vec<String> = self
-            .keys()
             .iter()
-            .map(|key| {
-                format!(
-                    "'{}' => {} - {}",
-                    key,
-                    self.get(key).unwrap(),
-                    self.get_attributes(key).unwrap()
-                )
-            })
+            .map(|(key, value, attrs)| format!("'{}' => {} - {}", key, value, attrs))
             .collect();
         let ret = ret.join("\n");
         write!(f, "{}", ret)
@@ -0,0 +1,299 @@
+use serde::de::value::{BorrowedStrDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{self, Deserialize, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::hash::Hash;
+use crate::hash_serializer::SerdeError;
+use crate::types::{get_typename, HashValue};
+
+// Deserializes `T` from `hash`, the reverse of `hash_serializer::to_hash`.
+// Integers and floats convert to any width that holds the value, fields
+// named `node@attr` are read from the attribute `attr` of the node `node`.
+pub fn from_hash<'de, T: Deserialize<'de>>(hash: &'de Hash) -> Result<T, SerdeError> {
+    T::deserialize(HashDeserializer { hash })
+}
+
+pub fn from_value<'de, T: Deserialize<'de>>(value: &'de HashValue) -> Result<T, SerdeError> {
+    T::deserialize(ValueDeserializer { value })
+}
+
+#[derive(Clone, Copy)]
+struct ValueDeserializer<'de> {
+    value: &'de HashValue,
+}
+
+#[derive(Clone, Copy)]
+struct HashDeserializer<'de> {
+    hash: &'de Hash,
+}
+
+fn visit_seq<'de, I, V>(items: I, visitor: V) -> Result<V::Value, SerdeError>
+where
+    I: Iterator,
+    I::Item: IntoDeserializer<'de, SerdeError>,
+    V: Visitor<'de>,
+{
+    let mut seq = SeqDeserializer::new(items);
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+fn visit_map<'de, I, V>(entries: I, visitor: V) -> Result<V::Value, SerdeError>
+where
+    I: Iterator<Item = (&'de str, ValueDeserializer<'de>)>,
+    V: Visitor<'de>,
+{
+    let entries = entries.map(|(key, value)| (BorrowedStrDeserializer::new(key), value));
+    let mut map = MapDeserializer::new(entries);
+    let value = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(value)
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for HashDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            HashValue::Bool(x) => visitor.visit_bool(*x),
+            HashValue::Char(x) => visitor.visit_char(*x),
+            HashValue::Int8(x) => visitor.visit_i8(*x),
+            HashValue::UInt8(x) => visitor.visit_u8(*x),
+            HashValue::Int16(x) => visitor.visit_i16(*x),
+            HashValue::UInt16(x) => visitor.visit_u16(*x),
+            HashValue::Int32(x) => visitor.visit_i32(*x),
+            HashValue::UInt32(x) => visitor.visit_u32(*x),
+            HashValue::Int64(x) => visitor.visit_i64(*x),
+            HashValue::UInt64(x) => visitor.visit_u64(*x),
+            HashValue::Float32(x) => visitor.visit_f32(*x),
+            HashValue::Float64(x) => visitor.visit_f64(*x),
+            HashValue::String(x) => visitor.visit_borrowed_str(x),
+            HashValue::VectorBool(x) => visit_seq(x.iter().copied(), visitor),
+            HashValue::VectorChar(x) => visit_seq(x.iter().copied(), visitor),
+            HashValue::VectorInt8(x) => visit_seq(x.iter().copied(), visitor),
+            HashValue::VectorUInt8(x) | HashValue::ByteArray(x) => {
+                visit_seq(x.iter().copied(), visitor)
+            }
+            HashValue::VectorInt16(x) => visit_seq(x.iter().copied(), visitor),
+            HashValue::VectorUInt16(x) => visit_seq(x.iter().copied(), visitor),
+            HashValue::VectorInt32(x) => visit_seq(x.iter().copied(), visitor),
+            HashValue::VectorUInt32(x) => visit_seq(x.iter().copied(), visitor),
+            HashValue::VectorInt64(x) => visit_seq(x.iter().copied(), visitor),
+            HashValue::VectorUInt64(x) => visit_seq(x.iter().copied(), visitor),
+            HashValue::VectorFloat32(x) => visit_seq(x.iter().copied(), visitor),
+            HashValue::VectorFloat64(x) => visit_seq(x.iter().copied(), visitor),
+            HashValue::VectorString(x) => {
+                visit_seq(x.iter().map(|s| BorrowedStrDeserializer::new(s)), visitor)
+            }
+            HashValue::Hash(hash) => HashDeserializer { hash }.deserialize_any(visitor),
+            HashValue::VectorHash(x) => {
+                visit_seq(x.iter().map(|hash| HashDeserializer { hash }), visitor)
+            }
+            HashValue::None => visitor.visit_unit(),
+            HashValue::VectorNone(x) => visit_seq(std::iter::repeat_n((), *x), visitor),
+            value => Err(SerdeError::Unsupported {
+                type_: get_typename(value),
+            }),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            HashValue::None => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            HashValue::VectorUInt8(x) | HashValue::ByteArray(x) => visitor.visit_borrowed_bytes(x),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.value {
+            HashValue::Hash(hash) => {
+                HashDeserializer { hash }.deserialize_struct(name, fields, visitor)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.value {
+            HashValue::String(variant) => {
+                visitor.visit_enum(BorrowedStrDeserializer::<SerdeError>::new(variant))
+            }
+            HashValue::Hash(hash) => {
+                HashDeserializer { hash }.deserialize_enum(name, variants, visitor)
+            }
+            value => Err(de::Error::invalid_type(unexpected(value), &visitor)),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+fn unexpected(value: &HashValue) -> de::Unexpected<'_> {
+    de::Unexpected::Other(get_typename(value))
+}
+
+impl<'de> de::Deserializer<'de> for HashDeserializer<'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let entries = self
+            .hash
+            .iter()
+            .map(|(key, value, _)| (key, ValueDeserializer { value }));
+        visit_map(entries, visitor)
+    }
+
+    // also yields the `node@attr` fields from the attributes
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let hash = self.hash;
+        let attrs = fields.iter().filter_map(|field| {
+            let (node, attr) = field.split_once('@')?;
+            let value = hash.get_key(node)?.attrs.get(attr)?;
+            Some((*field, ValueDeserializer { value }))
+        });
+        let entries = hash
+            .iter()
+            .map(|(key, value, _)| (key, ValueDeserializer { value }));
+        visit_map(entries.chain(attrs), visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // a single node keyed by the variant name
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let mut nodes = self.hash.iter();
+        match (nodes.next(), nodes.next()) {
+            (Some((variant, value, _)), None) => {
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            }
+            _ => Err(de::Error::invalid_length(
+                self.hash.len(),
+                &"a single variant",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map identifier
+        ignored_any
+    }
+}
+
+struct EnumDeserializer<'de> {
+    variant: &'de str,
+    value: &'de HashValue,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer<'de> {
+    type Error = SerdeError;
+    type Variant = ValueDeserializer<'de>;
+
+    fn variant_seed<S: de::DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, Self::Variant), SerdeError> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::<SerdeError>::new(self.variant))?;
+        Ok((variant, ValueDeserializer { value: self.value }))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for ValueDeserializer<'de> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: de::DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<S::Value, SerdeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
@@ -0,0 +1,126 @@
+use crate::attributes::Attributes;
+use crate::hash::Hash;
+use crate::types::HashValue;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueChange {
+    pub path: String,
+    pub old: HashValue,
+    pub new: HashValue,
+}
+
+// `old` is `None` for an added attribute, `new` for a removed one
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttributeChange {
+    pub path: String,
+    pub key: String,
+    pub old: Option<HashValue>,
+    pub new: Option<HashValue>,
+}
+
+// Differences between two hashes, see `Hash::diff`.
+// All lists are in the insertion order of the compared hashes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HashDiff {
+    // paths only in the new hash, with their value
+    pub added: Vec<(String, HashValue)>,
+    // paths only in the old hash, with their value
+    pub removed: Vec<(String, HashValue)>,
+    pub changed: Vec<ValueChange>,
+    // attribute changes of the nodes in both hashes
+    pub attributes: Vec<AttributeChange>,
+}
+
+impl HashDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.attributes.is_empty()
+    }
+
+    // Minimal hash turning the old hash into the new one when merged, e.g.
+    // for a reconfigure. It holds the added and changed values, removals
+    // and attribute changes can't be expressed. Returns `None` if `insert`
+    // refuses one of the paths, e.g. for keys containing `[`.
+    pub fn to_hash(&self) -> Option<Hash> {
+        let mut hash = Hash::new();
+        for (path, value) in &self.added {
+            if !hash.insert(path, value.clone()) {
+                return None;
+            }
+        }
+        for change in &self.changed {
+            if !hash.insert(&change.path, change.new.clone()) {
+                return None;
+            }
+        }
+        Some(hash)
+    }
+}
+
+impl Hash {
+    // Compares `old` and `new` path by path. Nested hashes are descended
+    // into, any other value is compared as a whole, vectors of hashes
+    // included. A value changing type is a change.
+    pub fn diff(old: &Hash, new: &Hash) -> HashDiff {
+        let mut diff = HashDiff::default();
+        diff_into(old, new, "", &mut diff);
+        diff
+    }
+}
+
+fn diff_into(old: &Hash, new: &Hash, prefix: &str, diff: &mut HashDiff) {
+    for (key, old_value, old_attrs) in old {
+        let path = format!("{prefix}{key}");
+        let new_node = match new.get_key(key) {
+            Some(node) => node,
+            None => {
+                diff.removed.push((path, old_value.clone()));
+                continue;
+            }
+        };
+        diff_attributes(&path, old_attrs, &new_node.attrs, diff);
+        match (old_value, &new_node.value) {
+            (HashValue::Hash(old), HashValue::Hash(new)) => {
+                diff_into(old, new, &format!("{path}."), diff)
+            }
+            (old, new) if old != new => diff.changed.push(ValueChange {
+                path,
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            _ => {}
+        }
+    }
+    for (key, new_value, _) in new {
+        if old.get_key(key).is_none() {
+            diff.added
+                .push((format!("{prefix}{key}"), new_value.clone()));
+        }
+    }
+}
+
+fn diff_attributes(path: &str, old: &Attributes, new: &Attributes, diff: &mut HashDiff) {
+    for (key, old_value) in old {
+        let new_value = new.get(key);
+        if new_value != Some(old_value) {
+            diff.attributes.push(AttributeChange {
+                path: path.to_string(),
+                key: key.to_string(),
+                old: Some(old_value.clone()),
+                new: new_value.cloned(),
+            });
+        }
+    }
+    for (key, new_value) in new {
+        if old.get(key).is_none() {
+            diff.attributes.push(AttributeChange {
+                path: path.to_string(),
+                key: key.to_string(),
+                old: None,
+                new: Some(new_value.clone()),
+            });
+        }
+    }
+}
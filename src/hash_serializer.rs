@@ -0,0 +1,574 @@
+use std::fmt;
+
+use serde::ser::{self, Serialize};
+
+use crate::attributes::Attributes;
+use crate::hash::Hash;
+use crate::schema::SchemaType;
+use crate::types::{get_typename, HashValue};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SerdeError {
+    Message(String),
+    // hashes are keyed by strings
+    KeyMustBeString,
+    // Karabo vectors hold a single type
+    MixedSequence {
+        first: &'static str,
+        found: &'static str,
+    },
+    // e.g. a vector of vectors
+    UnsupportedElement {
+        type_: &'static str,
+    },
+    // complex numbers and schemas have no serde mapping
+    Unsupported {
+        type_: &'static str,
+    },
+    // the top level value of `to_hash` has to be a struct or a map
+    NotAHash {
+        found: &'static str,
+    },
+    // a `node@attr` field without a `node` field
+    MissingNode {
+        node: String,
+        attr: String,
+    },
+    // the element type of an empty sequence is unknown, see `typed`
+    EmptySequence,
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SerdeError::Message(msg) => write!(f, "{msg}"),
+            SerdeError::KeyMustBeString => write!(f, "keys must be strings"),
+            SerdeError::MixedSequence { first, found } => {
+                write!(f, "sequence of {first} contains a {found}")
+            }
+            SerdeError::UnsupportedElement { type_ } => {
+                write!(f, "{type_} can't be an element of a sequence")
+            }
+            SerdeError::Unsupported { type_ } => write!(f, "{type_} is not supported"),
+            SerdeError::NotAHash { found } => {
+                write!(f, "expected a struct or a map, found {found}")
+            }
+            SerdeError::MissingNode { node, attr } => {
+                write!(f, "attribute '{attr}' of missing node '{node}'")
+            }
+            SerdeError::EmptySequence => write!(f, "empty sequence without a type"),
+        }
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError::Message(msg.to_string())
+    }
+}
+
+impl serde::de::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError::Message(msg.to_string())
+    }
+}
+
+// Serializes `value` into a hash, it has to be a struct or a map.
+//
+// Structs and maps become hashes, sequences become the vector of their
+// element type, a sequence of structs a vector of hashes, and integers keep
+// their width, e.g. a `u16` is a `UINT16`. `None` and `()` are `NONE`, unit
+// variants strings and other variants single node hashes keyed by the
+// variant name.
+//
+// An empty sequence has no element type, it is an `EmptySequence` error
+// unless the field gives the type with `typed` or `rows`.
+//
+// Fields named `node@attr`, e.g. with `#[serde(rename = "position@unit")]`,
+// are set as the attribute `attr` of the field `node`.
+pub fn to_hash<T: Serialize + ?Sized>(value: &T) -> Result<Hash, SerdeError> {
+    match to_value(value)? {
+        HashValue::Hash(hash) => Ok(hash),
+        value => Err(SerdeError::NotAHash {
+            found: get_typename(&value),
+        }),
+    }
+}
+
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<HashValue, SerdeError> {
+    typed_only(value.serialize(Serializer)?)
+}
+
+// Prefix of the newtype names that carry the Karabo type of a sequence,
+// also spelled out in `vector_types!` and `rows`
+const TYPED: &str = "$karabo_wasm::";
+
+macro_rules! vector_types {
+    ($($variant:ident => $name:literal),*) => {
+        fn typed_name(type_: &str) -> Option<&'static str> {
+            match type_ {
+                $($name => Some(concat!("$karabo_wasm::", $name)),)*
+                _ => None,
+            }
+        }
+
+        fn empty_vector(type_: &str) -> Option<HashValue> {
+            match type_ {
+                $($name => Some(HashValue::$variant(Vec::new())),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+vector_types!(
+    VectorBool => "VECTOR_BOOL",
+    VectorChar => "VECTOR_CHAR",
+    VectorInt8 => "VECTOR_INT8",
+    VectorUInt8 => "VECTOR_UINT8",
+    VectorInt16 => "VECTOR_INT16",
+    VectorUInt16 => "VECTOR_UINT16",
+    VectorInt32 => "VECTOR_INT32",
+    VectorUInt32 => "VECTOR_UINT32",
+    VectorInt64 => "VECTOR_INT64",
+    VectorUInt64 => "VECTOR_UINT64",
+    VectorFloat32 => "VECTOR_FLOAT",
+    VectorFloat64 => "VECTOR_DOUBLE",
+    VectorString => "VECTOR_STRING",
+    VectorHash => "VECTOR_HASH"
+);
+
+// `serialize_with` helper giving a vector its Karabo type even when empty,
+// e.g. `#[serde(serialize_with = "hash_serializer::typed")]` on a `Vec<f64>`.
+// Other serializers see the vector as is.
+pub fn typed<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + SchemaType,
+    S: ser::Serializer,
+{
+    match typed_name(T::VALUE_TYPE) {
+        Some(name) => serializer.serialize_newtype_struct(name, value),
+        None => Err(ser::Error::custom(format!(
+            "{} is not a vector",
+            T::VALUE_TYPE
+        ))),
+    }
+}
+
+// Same as `typed` for a sequence of structs or maps, a `VECTOR_HASH`
+pub fn rows<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + ?Sized,
+    S: ser::Serializer,
+{
+    serializer.serialize_newtype_struct("$karabo_wasm::VECTOR_HASH", value)
+}
+
+// empty sequences are serialized as `VECTOR_NONE` until they are stored,
+// they are refused there unless `typed` gave them a type
+fn typed_only(value: HashValue) -> Result<HashValue, SerdeError> {
+    match value {
+        HashValue::VectorNone(0) => Err(SerdeError::EmptySequence),
+        value => Ok(value),
+    }
+}
+
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = HashValue;
+    type Error = SerdeError;
+    type SerializeSeq = SerializeVec;
+    type SerializeTuple = SerializeVec;
+    type SerializeTupleStruct = SerializeVec;
+    type SerializeTupleVariant = SerializeTupleVariant;
+    type SerializeMap = SerializeHash;
+    type SerializeStruct = SerializeHash;
+    type SerializeStructVariant = SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::Int8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::Int16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::Int32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::Int64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::UInt8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::UInt16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::UInt32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::UInt64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::Float32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::Float64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::ByteArray(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<HashValue, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<HashValue, SerdeError> {
+        Ok(HashValue::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<HashValue, SerdeError> {
+        let type_ = match name.strip_prefix(TYPED) {
+            Some(type_) => type_,
+            None => return value.serialize(self),
+        };
+        match value.serialize(self)? {
+            HashValue::VectorNone(0) => Ok(empty_vector(type_).expect("a vector type")),
+            value if get_typename(&value) == type_ => Ok(value),
+            value => Err(SerdeError::MixedSequence {
+                first: type_,
+                found: get_typename(&value),
+            }),
+        }
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<HashValue, SerdeError> {
+        Ok(variant_hash(variant, typed_only(value.serialize(self)?)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeVec, SerdeError> {
+        Ok(SerializeVec {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeVec, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeTupleVariant, SerdeError> {
+        Ok(SerializeTupleVariant {
+            variant,
+            vec: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeHash, SerdeError> {
+        Ok(SerializeHash::default())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<SerializeHash, SerdeError> {
+        Ok(SerializeHash::default())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeStructVariant, SerdeError> {
+        Ok(SerializeStructVariant {
+            variant,
+            hash: SerializeHash::default(),
+        })
+    }
+}
+
+fn variant_hash(variant: &str, value: HashValue) -> HashValue {
+    let mut hash = Hash::new();
+    hash.insert_index_attrs(variant, value, Attributes::new());
+    HashValue::Hash(hash)
+}
+
+// picks the vector type from the first element, all have to be of that type
+fn into_vector(items: Vec<HashValue>) -> Result<HashValue, SerdeError> {
+    let first = match items.first() {
+        Some(first) => get_typename(first),
+        None => return Ok(HashValue::VectorNone(0)),
+    };
+    macro_rules! collect {
+        ($($scalar:ident => $vector:ident),*) => {
+            match items[0] {
+                $(HashValue::$scalar(_) => items
+                    .into_iter()
+                    .map(|item| match item {
+                        HashValue::$scalar(x) => Ok(x),
+                        item => Err(SerdeError::MixedSequence {
+                            first,
+                            found: get_typename(&item),
+                        }),
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(HashValue::$vector),)*
+                HashValue::None => match items.iter().find(|item| !item.is_none()) {
+                    Some(item) => Err(SerdeError::MixedSequence {
+                        first,
+                        found: get_typename(item),
+                    }),
+                    None => Ok(HashValue::VectorNone(items.len())),
+                },
+                _ => Err(SerdeError::UnsupportedElement { type_: first }),
+            }
+        };
+    }
+    collect!(
+        Bool => VectorBool,
+        Char => VectorChar,
+        Int8 => VectorInt8,
+        UInt8 => VectorUInt8,
+        Int16 => VectorInt16,
+        UInt16 => VectorUInt16,
+        Int32 => VectorInt32,
+        UInt32 => VectorUInt32,
+        Int64 => VectorInt64,
+        UInt64 => VectorUInt64,
+        Float32 => VectorFloat32,
+        Float64 => VectorFloat64,
+        String => VectorString,
+        Hash => VectorHash
+    )
+}
+
+pub struct SerializeVec {
+    items: Vec<HashValue>,
+}
+
+impl ser::SerializeSeq for SerializeVec {
+    type Ok = HashValue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.items.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<HashValue, SerdeError> {
+        into_vector(self.items)
+    }
+}
+
+impl ser::SerializeTuple for SerializeVec {
+    type Ok = HashValue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<HashValue, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeVec {
+    type Ok = HashValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<HashValue, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+pub struct SerializeTupleVariant {
+    variant: &'static str,
+    vec: SerializeVec,
+}
+
+impl ser::SerializeTupleVariant for SerializeTupleVariant {
+    type Ok = HashValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(&mut self.vec, value)
+    }
+
+    fn end(self) -> Result<HashValue, SerdeError> {
+        Ok(variant_hash(
+            self.variant,
+            typed_only(into_vector(self.vec.items)?)?,
+        ))
+    }
+}
+
+#[derive(Default)]
+pub struct SerializeHash {
+    hash: Hash,
+    // `node@attr` entries, set once all the nodes are known
+    attrs: Vec<(String, String, HashValue)>,
+    key: Option<String>,
+}
+
+impl SerializeHash {
+    fn insert(&mut self, key: &str, value: HashValue) -> Result<(), SerdeError> {
+        let value = typed_only(value)?;
+        match key.split_once('@') {
+            Some((node, attr)) => self.attrs.push((node.to_string(), attr.to_string(), value)),
+            None => self.hash.insert_index_attrs(key, value, Attributes::new()),
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Hash, SerdeError> {
+        // nodes are stored with their literal key, which can contain `.`
+        for (node, attr, value) in self.attrs {
+            match self.hash.get_key_mut(&node) {
+                Some(target) => target.attrs.insert(&attr, value),
+                None => return Err(SerdeError::MissingNode { node, attr }),
+            }
+        }
+        Ok(self.hash)
+    }
+}
+
+impl ser::SerializeMap for SerializeHash {
+    type Ok = HashValue;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.key = match key.serialize(Serializer)? {
+            HashValue::String(key) => Some(key),
+            HashValue::Char(key) => Some(key.to_string()),
+            _ => return Err(SerdeError::KeyMustBeString),
+        };
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| SerdeError::Message("value without a key".to_string()))?;
+        self.insert(&key, value.serialize(Serializer)?)
+    }
+
+    fn end(self) -> Result<HashValue, SerdeError> {
+        self.finish().map(HashValue::Hash)
+    }
+}
+
+impl ser::SerializeStruct for SerializeHash {
+    type Ok = HashValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.insert(key, value.serialize(Serializer)?)
+    }
+
+    fn end(self) -> Result<HashValue, SerdeError> {
+        self.finish().map(HashValue::Hash)
+    }
+}
+
+pub struct SerializeStructVariant {
+    variant: &'static str,
+    hash: SerializeHash,
+}
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = HashValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        ser::SerializeStruct::serialize_field(&mut self.hash, key, value)
+    }
+
+    fn end(self) -> Result<HashValue, SerdeError> {
+        Ok(variant_hash(
+            self.variant,
+            HashValue::Hash(self.hash.finish()?),
+        ))
+    }
+}
@@ -0,0 +1,468 @@
+use std::marker::PhantomData;
+
+use crate::attributes::Attributes;
+use crate::binary_readers::{
+    read_bool, read_cf32, read_cf64, read_char, read_f32, read_f64, read_i16, read_i32, read_i64,
+    read_i8, read_len, read_none, read_u16, read_u32, read_u64, read_u8, DecodeError,
+    DecodeOptions, Decoder,
+};
+use crate::hash::Hash;
+use crate::schema::Schema;
+use crate::types::{Complex, HashValue};
+
+// Element types that can be read in place from their little endian bytes
+pub trait LeElement: Copy + 'static {
+    const SIZE: usize;
+    fn from_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! le_element {
+    ($($t:ty),*) => {$(
+        impl LeElement for $t {
+            const SIZE: usize = std::mem::size_of::<$t>();
+            fn from_le(bytes: &[u8]) -> Self {
+                let mut sized = [0u8; std::mem::size_of::<$t>()];
+                sized.copy_from_slice(bytes);
+                <$t>::from_le_bytes(sized)
+            }
+        }
+    )*};
+}
+
+le_element!(i8, u8, i16, u16, i32, u32, i64, u64, f32, f64);
+
+impl LeElement for bool {
+    const SIZE: usize = 1;
+    fn from_le(bytes: &[u8]) -> Self {
+        bytes[0] != 0
+    }
+}
+
+impl LeElement for char {
+    const SIZE: usize = 1;
+    fn from_le(bytes: &[u8]) -> Self {
+        bytes[0] as char
+    }
+}
+
+impl LeElement for Complex<f32> {
+    const SIZE: usize = 8;
+    fn from_le(bytes: &[u8]) -> Self {
+        Complex::new(f32::from_le(&bytes[..4]), f32::from_le(&bytes[4..]))
+    }
+}
+
+impl LeElement for Complex<f64> {
+    const SIZE: usize = 16;
+    fn from_le(bytes: &[u8]) -> Self {
+        Complex::new(f64::from_le(&bytes[..8]), f64::from_le(&bytes[8..]))
+    }
+}
+
+// A vector left in its wire representation, elements are decoded on access
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VectorView<'a, T> {
+    bytes: &'a [u8],
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: LeElement> VectorView<'a, T> {
+    fn new(bytes: &'a [u8]) -> Self {
+        VectorView {
+            bytes,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len() / T::SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn get(&self, index: usize) -> Option<T> {
+        let start = index.checked_mul(T::SIZE)?;
+        let chunk = self.bytes.get(start..start.checked_add(T::SIZE)?)?;
+        Some(T::from_le(chunk))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + 'a {
+        self.bytes.chunks_exact(T::SIZE).map(T::from_le)
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueView<'a> {
+    Bool(bool),
+    VectorBool(VectorView<'a, bool>),
+    Char(char),
+    VectorChar(VectorView<'a, char>),
+    UInt8(u8),
+    VectorUInt8(VectorView<'a, u8>),
+    Int8(i8),
+    VectorInt8(VectorView<'a, i8>),
+    UInt16(u16),
+    VectorUInt16(VectorView<'a, u16>),
+    Int16(i16),
+    VectorInt16(VectorView<'a, i16>),
+    UInt32(u32),
+    VectorUInt32(VectorView<'a, u32>),
+    Int32(i32),
+    VectorInt32(VectorView<'a, i32>),
+    UInt64(u64),
+    VectorUInt64(VectorView<'a, u64>),
+    Int64(i64),
+    VectorInt64(VectorView<'a, i64>),
+    Float32(f32),
+    VectorFloat32(VectorView<'a, f32>),
+    Float64(f64),
+    VectorFloat64(VectorView<'a, f64>),
+    ComplexFloat32(Complex<f32>),
+    VectorComplexFloat32(VectorView<'a, Complex<f32>>),
+    ComplexFloat64(Complex<f64>),
+    VectorComplexFloat64(VectorView<'a, Complex<f64>>),
+    String(&'a str),
+    VectorString(Vec<&'a str>),
+    Hash(HashView<'a>),
+    VectorHash(Vec<HashView<'a>>),
+    Schema {
+        class_id: &'a str,
+        hash: HashView<'a>,
+    },
+    None,
+    VectorNone(usize),
+    ByteArray(&'a [u8]),
+}
+
+impl<'a> ValueView<'a> {
+    pub fn as_str(&self) -> Option<&'a str> {
+        match *self {
+            ValueView::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            ValueView::ByteArray(value) => Some(value),
+            ValueView::VectorUInt8(value) => Some(value.as_bytes()),
+            _ => None,
+        }
+    }
+
+    pub fn as_hash(&self) -> Option<&HashView<'a>> {
+        match self {
+            ValueView::Hash(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn to_value(&self) -> HashValue {
+        match self {
+            ValueView::Bool(x) => HashValue::Bool(*x),
+            ValueView::VectorBool(x) => HashValue::VectorBool(x.to_vec()),
+            ValueView::Char(x) => HashValue::Char(*x),
+            ValueView::VectorChar(x) => HashValue::VectorChar(x.to_vec()),
+            ValueView::UInt8(x) => HashValue::UInt8(*x),
+            ValueView::VectorUInt8(x) => HashValue::VectorUInt8(x.as_bytes().to_vec()),
+            ValueView::Int8(x) => HashValue::Int8(*x),
+            ValueView::VectorInt8(x) => HashValue::VectorInt8(x.to_vec()),
+            ValueView::UInt16(x) => HashValue::UInt16(*x),
+            ValueView::VectorUInt16(x) => HashValue::VectorUInt16(x.to_vec()),
+            ValueView::Int16(x) => HashValue::Int16(*x),
+            ValueView::VectorInt16(x) => HashValue::VectorInt16(x.to_vec()),
+            ValueView::UInt32(x) => HashValue::UInt32(*x),
+            ValueView::VectorUInt32(x) => HashValue::VectorUInt32(x.to_vec()),
+            ValueView::Int32(x) => HashValue::Int32(*x),
+            ValueView::VectorInt32(x) => HashValue::VectorInt32(x.to_vec()),
+            ValueView::UInt64(x) => HashValue::UInt64(*x),
+            ValueView::VectorUInt64(x) => HashValue::VectorUInt64(x.to_vec()),
+            ValueView::Int64(x) => HashValue::Int64(*x),
+            ValueView::VectorInt64(x) => HashValue::VectorInt64(x.to_vec()),
+            ValueView::Float32(x) => HashValue::Float32(*x),
+            ValueView::VectorFloat32(x) => HashValue::VectorFloat32(x.to_vec()),
+            ValueView::Float64(x) => HashValue::Float64(*x),
+            ValueView::VectorFloat64(x) => HashValue::VectorFloat64(x.to_vec()),
+            ValueView::ComplexFloat32(x) => HashValue::ComplexFloat32(*x),
+            ValueView::VectorComplexFloat32(x) => HashValue::VectorComplexFloat32(x.to_vec()),
+            ValueView::ComplexFloat64(x) => HashValue::ComplexFloat64(*x),
+            ValueView::VectorComplexFloat64(x) => HashValue::VectorComplexFloat64(x.to_vec()),
+            ValueView::String(x) => HashValue::String(x.to_string()),
+            ValueView::VectorString(x) => {
+                HashValue::VectorString(x.iter().map(|s| s.to_string()).collect())
+            }
+            ValueView::Hash(x) => HashValue::Hash(x.to_hash()),
+            ValueView::VectorHash(x) => {
+                HashValue::VectorHash(x.iter().map(|h| h.to_hash()).collect())
+            }
+            ValueView::Schema { class_id, hash } => {
+                HashValue::Schema(Schema::new(class_id.to_string(), hash.to_hash()))
+            }
+            ValueView::None => HashValue::None,
+            ValueView::VectorNone(x) => HashValue::VectorNone(*x),
+            ValueView::ByteArray(x) => HashValue::ByteArray(x.to_vec()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AttributesView<'a> {
+    store: Vec<(&'a str, ValueView<'a>)>,
+}
+
+impl<'a> AttributesView<'a> {
+    pub fn keys(&self) -> Vec<&'a str> {
+        self.store.iter().map(|(key, _)| *key).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&ValueView<'a>> {
+        self.store
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn to_attributes(&self) -> Attributes {
+        let mut attrs = Attributes::new();
+        for (key, value) in self.store.iter() {
+            attrs.insert(key, value.to_value());
+        }
+        attrs
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeView<'a> {
+    pub key: &'a str,
+    pub value: ValueView<'a>,
+    pub attrs: AttributesView<'a>,
+}
+
+// A Hash borrowing keys, strings and vectors from the buffer it was parsed
+// from. The whole buffer is validated by `parse`, lookups cannot fail later.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HashView<'a> {
+    store: Vec<NodeView<'a>>,
+}
+
+impl<'a> HashView<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, DecodeError> {
+        HashView::parse_with(data, &DecodeOptions::default())
+    }
+
+    // `data` must contain exactly one hash
+    pub fn parse_with(data: &'a [u8], opts: &DecodeOptions) -> Result<Self, DecodeError> {
+        let mut buf = Decoder::new(data, opts);
+        buf.enter()?;
+        let hash = view_hash(&mut buf)?;
+        if buf.remaining() != 0 {
+            return Err(DecodeError::LengthMismatch {
+                length: data.len() as u64,
+                offset: buf.offset,
+                path: String::new(),
+            });
+        }
+        Ok(hash)
+    }
+
+    pub fn keys(&self) -> Vec<&'a str> {
+        self.store.iter().map(|node| node.key).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    pub fn nodes(&self) -> &[NodeView<'a>] {
+        &self.store
+    }
+
+    fn get_node(&self, path: &str) -> Option<&NodeView<'a>> {
+        let (key, rest) = match path.split_once('.') {
+            Some((key, rest)) => (key, Some(rest)),
+            None => (path, None),
+        };
+        let node = self.store.iter().find(|node| node.key == key)?;
+        match (rest, &node.value) {
+            (None, _) => Some(node),
+            (Some(rest), ValueView::Hash(hash)) => hash.get_node(rest),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&ValueView<'a>> {
+        self.get_node(path).map(|node| &node.value)
+    }
+
+    pub fn get_attributes(&self, path: &str) -> Option<&AttributesView<'a>> {
+        self.get_node(path).map(|node| &node.attrs)
+    }
+
+    pub fn to_hash(&self) -> Hash {
+        let mut hash = Hash::new();
+        for node in self.store.iter() {
+            hash.insert_index_attrs(node.key, node.value.to_value(), node.attrs.to_attributes());
+        }
+        hash
+    }
+}
+
+fn view_array<'a>(buf: &mut Decoder<&'a [u8]>, chunk_size: usize) -> Result<&'a [u8], DecodeError> {
+    let offset = buf.offset;
+    let size = read_len(buf, buf.opts.max_vector_len)?;
+    let nbytes = size
+        .checked_mul(chunk_size)
+        .ok_or_else(|| buf.oversized(size as u64 * chunk_size as u64, offset))?;
+    buf.take(nbytes)
+}
+
+fn view_vector<'a, T: LeElement>(
+    buf: &mut Decoder<&'a [u8]>,
+) -> Result<VectorView<'a, T>, DecodeError> {
+    Ok(VectorView::new(view_array(buf, T::SIZE)?))
+}
+
+fn view_str<'a>(buf: &mut Decoder<&'a [u8]>, size: usize) -> Result<&'a str, DecodeError> {
+    let offset = buf.offset;
+    let arr = buf.take(size)?;
+    std::str::from_utf8(arr).map_err(|_| buf.invalid_utf8(offset))
+}
+
+fn view_string<'a>(buf: &mut Decoder<&'a [u8]>) -> Result<&'a str, DecodeError> {
+    let size = read_len(buf, buf.opts.max_string_len)?;
+    view_str(buf, size)
+}
+
+fn view_key<'a>(buf: &mut Decoder<&'a [u8]>) -> Result<&'a str, DecodeError> {
+    let size = read_u8(buf)?;
+    view_str(buf, size.into())
+}
+
+fn view_vstring<'a>(buf: &mut Decoder<&'a [u8]>) -> Result<Vec<&'a str>, DecodeError> {
+    let size = read_len(buf, buf.opts.max_vector_len)?;
+    let mut arr = Vec::new();
+    for index in 0..size {
+        let mark = buf.push_path_suffix(&format!("[{index}]"));
+        arr.push(view_string(buf)?);
+        buf.pop_path(mark);
+    }
+    Ok(arr)
+}
+
+fn view_vhash<'a>(buf: &mut Decoder<&'a [u8]>) -> Result<Vec<HashView<'a>>, DecodeError> {
+    let size = read_len(buf, buf.opts.max_vector_len)?;
+    let mut arr = Vec::new();
+    buf.enter()?;
+    for index in 0..size {
+        let mark = buf.push_path_suffix(&format!("[{index}]"));
+        arr.push(view_hash(buf)?);
+        buf.pop_path(mark);
+    }
+    buf.leave();
+    Ok(arr)
+}
+
+fn view_hash<'a>(buf: &mut Decoder<&'a [u8]>) -> Result<HashView<'a>, DecodeError> {
+    let mut store = Vec::new();
+    let nkeys = read_u32(buf)?;
+    for _ in 0..nkeys {
+        let key = view_key(buf)?;
+        let mark = buf.push_path(key);
+        let value_type = read_u32(buf)?;
+        let nattrs = read_u32(buf)?;
+        let mut attrs = AttributesView::default();
+        for _ in 0..nattrs {
+            let attr_key = view_key(buf)?;
+            let attr_mark = buf.push_path_suffix(&format!("@{attr_key}"));
+            let attr_type = read_u32(buf)?;
+            let attr_value = view_value(buf, attr_type)?;
+            attrs.store.push((attr_key, attr_value));
+            buf.pop_path(attr_mark);
+        }
+        let value = view_value(buf, value_type)?;
+        buf.pop_path(mark);
+        store.push(NodeView { key, value, attrs });
+    }
+    Ok(HashView { store })
+}
+
+fn view_value<'a>(buf: &mut Decoder<&'a [u8]>, type_: u32) -> Result<ValueView<'a>, DecodeError> {
+    match type_ {
+        0 => Ok(ValueView::Bool(read_bool(buf)?)),
+        1 => Ok(ValueView::VectorBool(view_vector(buf)?)),
+        2 => Ok(ValueView::Char(read_char(buf)?)),
+        3 => Ok(ValueView::VectorChar(view_vector(buf)?)),
+        4 => Ok(ValueView::Int8(read_i8(buf)?)),
+        5 => Ok(ValueView::VectorInt8(view_vector(buf)?)),
+        6 => Ok(ValueView::UInt8(read_u8(buf)?)),
+        7 => Ok(ValueView::VectorUInt8(view_vector(buf)?)),
+        8 => Ok(ValueView::Int16(read_i16(buf)?)),
+        9 => Ok(ValueView::VectorInt16(view_vector(buf)?)),
+        10 => Ok(ValueView::UInt16(read_u16(buf)?)),
+        11 => Ok(ValueView::VectorUInt16(view_vector(buf)?)),
+        12 => Ok(ValueView::Int32(read_i32(buf)?)),
+        13 => Ok(ValueView::VectorInt32(view_vector(buf)?)),
+        14 => Ok(ValueView::UInt32(read_u32(buf)?)),
+        15 => Ok(ValueView::VectorUInt32(view_vector(buf)?)),
+        16 => Ok(ValueView::Int64(read_i64(buf)?)),
+        17 => Ok(ValueView::VectorInt64(view_vector(buf)?)),
+        18 => Ok(ValueView::UInt64(read_u64(buf)?)),
+        19 => Ok(ValueView::VectorUInt64(view_vector(buf)?)),
+        20 => Ok(ValueView::Float32(read_f32(buf)?)),
+        21 => Ok(ValueView::VectorFloat32(view_vector(buf)?)),
+        22 => Ok(ValueView::Float64(read_f64(buf)?)),
+        23 => Ok(ValueView::VectorFloat64(view_vector(buf)?)),
+        24 => Ok(ValueView::ComplexFloat32(read_cf32(buf)?)),
+        25 => Ok(ValueView::VectorComplexFloat32(view_vector(buf)?)),
+        26 => Ok(ValueView::ComplexFloat64(read_cf64(buf)?)),
+        27 => Ok(ValueView::VectorComplexFloat64(view_vector(buf)?)),
+        28 => Ok(ValueView::String(view_string(buf)?)),
+        29 => Ok(ValueView::VectorString(view_vstring(buf)?)),
+        30 => {
+            buf.enter()?;
+            let hash = view_hash(buf)?;
+            buf.leave();
+            Ok(ValueView::Hash(hash))
+        }
+        31 => Ok(ValueView::VectorHash(view_vhash(buf)?)),
+        32 => {
+            // the size prefix is not needed, the content is validated anyway
+            read_u32(buf)?;
+            let class_id = view_key(buf)?;
+            buf.enter()?;
+            let hash = view_hash(buf)?;
+            buf.leave();
+            Ok(ValueView::Schema { class_id, hash })
+        }
+        34 => read_none(buf).map(|_| ValueView::None),
+        35 => Ok(ValueView::VectorNone(view_array(buf, 4)?.len() / 4)),
+        37 => Ok(ValueView::ByteArray(view_array(buf, 1)?)),
+        _ => Err(DecodeError::UnknownType {
+            type_,
+            offset: buf.offset,
+            path: buf.path.clone(),
+        }),
+    }
+}
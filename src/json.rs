@@ -0,0 +1,754 @@
+use std::fmt;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::attributes::Attributes;
+use crate::hash::Hash;
+use crate::schema::Schema;
+use crate::types::{get_typename, Complex, HashValue};
+
+// JSON encoding of a hash.
+//
+// `Typed` keeps the types and attributes so that a hash round-trips exactly
+// through `Hash::from_json`. A hash is an object with one member per node,
+// in order:
+//
+//     {"key": {"type": "UINT8", "value": 1, "attrs": {"unit": {"type": "STRING", "value": "m"}}}}
+//
+// `attrs` is left out when there are none. `type` is the Karabo type name,
+// see `get_typename`, and `value` is encoded as:
+// - BOOL as a boolean, CHAR and STRING as strings
+// - integers as numbers, except INT64 and UINT64 which are strings as
+//   JavaScript numbers can't hold them
+// - FLOAT and DOUBLE as numbers, `"NaN"`, `"Infinity"` and `"-Infinity"`
+//   for values JSON numbers can't hold
+// - complex numbers as `[re, im]`
+// - vectors as arrays of their elements, VECTOR_NONE as an array of nulls
+// - HASH as a typed hash object, VECTOR_HASH as an array of them
+// - SCHEMA as `{"classId": "...", "hash": {...}}`
+// - NONE as null, BYTE_ARRAY as a base64 string
+//
+// `Plain` is for display: values are written as is, hashes as objects of
+// their values, attributes are dropped and non finite floats are null.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JsonMode {
+    #[default]
+    Typed,
+    Plain,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JsonError {
+    // malformed JSON
+    Syntax {
+        offset: usize,
+        expected: &'static str,
+    },
+    TooDeep {
+        offset: usize,
+    },
+    UnknownType {
+        type_: String,
+        path: String,
+    },
+    // not a typed node, or a value that does not match its type
+    InvalidValue {
+        type_: String,
+        path: String,
+    },
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonError::Syntax { offset, expected } => {
+                write!(f, "expected {expected} at offset {offset}")
+            }
+            JsonError::TooDeep { offset } => write!(f, "nested too deep at offset {offset}"),
+            JsonError::UnknownType { type_, path } => {
+                write!(f, "unknown type '{type_}' (key '{path}')")
+            }
+            JsonError::InvalidValue { type_, path } => {
+                write!(f, "invalid {type_} value (key '{path}')")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl Hash {
+    pub fn to_json(&self) -> String {
+        self.to_json_with(JsonMode::Typed)
+    }
+
+    pub fn to_json_with(&self, mode: JsonMode) -> String {
+        let mut out = String::new();
+        write_hash(&mut out, self, mode);
+        out
+    }
+
+    // Reads the `JsonMode::Typed` encoding
+    pub fn from_json(json: &str) -> Result<Hash, JsonError> {
+        let mut parser = Parser {
+            bytes: json.as_bytes(),
+            offset: 0,
+            depth: 0,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.offset != parser.bytes.len() {
+            return Err(parser.syntax("end of input"));
+        }
+        hash_from_json(&value, "")
+    }
+}
+
+fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_display<T: fmt::Display>(out: &mut String, value: T) {
+    let _ = write!(out, "{value}");
+}
+
+fn write_float<T: fmt::Debug>(out: &mut String, value: T, finite: bool, mode: JsonMode) {
+    match (finite, mode) {
+        // `Debug` is the shortest representation that reads back exactly
+        (true, _) => {
+            let _ = write!(out, "{value:?}");
+        }
+        (false, JsonMode::Typed) => {
+            let text = format!("{value:?}");
+            write_str(
+                out,
+                match text.as_str() {
+                    "inf" => "Infinity",
+                    "-inf" => "-Infinity",
+                    _ => "NaN",
+                },
+            );
+        }
+        (false, JsonMode::Plain) => out.push_str("null"),
+    }
+}
+
+fn write_array<T>(out: &mut String, values: &[T], mut write: impl FnMut(&mut String, &T)) {
+    out.push('[');
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write(out, value);
+    }
+    out.push(']');
+}
+
+fn write_complex<T: fmt::Debug + Copy>(
+    out: &mut String,
+    value: &Complex<T>,
+    finite: impl Fn(T) -> bool,
+    mode: JsonMode,
+) {
+    out.push('[');
+    write_float(out, value.re, finite(value.re), mode);
+    out.push(',');
+    write_float(out, value.im, finite(value.im), mode);
+    out.push(']');
+}
+
+fn write_hash(out: &mut String, hash: &Hash, mode: JsonMode) {
+    out.push('{');
+    for (i, (key, value, attrs)) in hash.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_str(out, key);
+        out.push(':');
+        match mode {
+            JsonMode::Typed => write_typed(out, value, attrs),
+            JsonMode::Plain => write_value(out, value, mode),
+        }
+    }
+    out.push('}');
+}
+
+fn write_typed(out: &mut String, value: &HashValue, attrs: &Attributes) {
+    out.push_str("{\"type\":");
+    write_str(out, get_typename(value));
+    out.push_str(",\"value\":");
+    write_value(out, value, JsonMode::Typed);
+    if !attrs.is_empty() {
+        out.push_str(",\"attrs\":{");
+        for (i, (key, value)) in attrs.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_str(out, key);
+            out.push(':');
+            write_typed(out, value, &Attributes::new());
+        }
+        out.push('}');
+    }
+    out.push('}');
+}
+
+fn write_value(out: &mut String, value: &HashValue, mode: JsonMode) {
+    // 64 bit integers are strings in the typed encoding
+    let write_i64 = |out: &mut String, x: &i64| match mode {
+        JsonMode::Typed => write_str(out, &x.to_string()),
+        JsonMode::Plain => write_display(out, x),
+    };
+    let write_u64 = |out: &mut String, x: &u64| match mode {
+        JsonMode::Typed => write_str(out, &x.to_string()),
+        JsonMode::Plain => write_display(out, x),
+    };
+    match value {
+        HashValue::Bool(x) => write_display(out, x),
+        HashValue::VectorBool(x) => write_array(out, x, |out, x| write_display(out, x)),
+        HashValue::Char(x) => write_str(out, &x.to_string()),
+        HashValue::VectorChar(x) => write_array(out, x, |out, x| write_str(out, &x.to_string())),
+        HashValue::Int8(x) => write_display(out, x),
+        HashValue::VectorInt8(x) => write_array(out, x, |out, x| write_display(out, x)),
+        HashValue::UInt8(x) => write_display(out, x),
+        HashValue::VectorUInt8(x) => write_array(out, x, |out, x| write_display(out, x)),
+        HashValue::Int16(x) => write_display(out, x),
+        HashValue::VectorInt16(x) => write_array(out, x, |out, x| write_display(out, x)),
+        HashValue::UInt16(x) => write_display(out, x),
+        HashValue::VectorUInt16(x) => write_array(out, x, |out, x| write_display(out, x)),
+        HashValue::Int32(x) => write_display(out, x),
+        HashValue::VectorInt32(x) => write_array(out, x, |out, x| write_display(out, x)),
+        HashValue::UInt32(x) => write_display(out, x),
+        HashValue::VectorUInt32(x) => write_array(out, x, |out, x| write_display(out, x)),
+        HashValue::Int64(x) => write_i64(out, x),
+        HashValue::VectorInt64(x) => write_array(out, x, write_i64),
+        HashValue::UInt64(x) => write_u64(out, x),
+        HashValue::VectorUInt64(x) => write_array(out, x, write_u64),
+        HashValue::Float32(x) => write_float(out, x, x.is_finite(), mode),
+        HashValue::VectorFloat32(x) => {
+            write_array(out, x, |out, x| write_float(out, x, x.is_finite(), mode))
+        }
+        HashValue::Float64(x) => write_float(out, x, x.is_finite(), mode),
+        HashValue::VectorFloat64(x) => {
+            write_array(out, x, |out, x| write_float(out, x, x.is_finite(), mode))
+        }
+        HashValue::ComplexFloat32(x) => write_complex(out, x, f32::is_finite, mode),
+        HashValue::VectorComplexFloat32(x) => {
+            write_array(out, x, |out, x| write_complex(out, x, f32::is_finite, mode))
+        }
+        HashValue::ComplexFloat64(x) => write_complex(out, x, f64::is_finite, mode),
+        HashValue::VectorComplexFloat64(x) => {
+            write_array(out, x, |out, x| write_complex(out, x, f64::is_finite, mode))
+        }
+        HashValue::String(x) => write_str(out, x),
+        HashValue::VectorString(x) => write_array(out, x, |out, x| write_str(out, x)),
+        HashValue::Hash(x) => write_hash(out, x, mode),
+        HashValue::VectorHash(x) => write_array(out, x, |out, x| write_hash(out, x, mode)),
+        HashValue::Schema(x) => {
+            out.push_str("{\"classId\":");
+            write_str(out, &x.class_id);
+            out.push_str(",\"hash\":");
+            write_hash(out, &x.hash, mode);
+            out.push('}');
+        }
+        HashValue::None => out.push_str("null"),
+        HashValue::VectorNone(x) => write_array(out, &vec![(); *x], |out, _| out.push_str("null")),
+        HashValue::ByteArray(x) => write_str(out, &base64_encode(x)),
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    for chunk in text.chunks(4) {
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 {
+            return None;
+        }
+        let mut n = 0u32;
+        for c in &chunk[..4 - padding] {
+            let digit = BASE64.iter().position(|b| b == c)?;
+            n = (n << 6) | digit as u32;
+        }
+        n <<= 6 * padding;
+        let decoded = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        out.extend_from_slice(&decoded[..3 - padding]);
+    }
+    Some(out)
+}
+
+enum Json {
+    Null,
+    Bool(bool),
+    // kept as text, it's parsed once the type is known
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+// recursion limit of the parser
+const MAX_DEPTH: usize = 512;
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn syntax(&self, expected: &'static str) -> JsonError {
+        JsonError::Syntax {
+            offset: self.offset,
+            expected,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.offset) {
+            self.offset += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.bytes.get(self.offset).copied()
+    }
+
+    fn expect(&mut self, byte: u8, expected: &'static str) -> Result<(), JsonError> {
+        match self.peek() {
+            Some(b) if b == byte => {
+                self.offset += 1;
+                Ok(())
+            }
+            _ => Err(self.syntax(expected)),
+        }
+    }
+
+    fn literal(&mut self, text: &'static str, value: Json) -> Result<Json, JsonError> {
+        if self.bytes[self.offset..].starts_with(text.as_bytes()) {
+            self.offset += text.len();
+            Ok(value)
+        } else {
+            Err(self.syntax(text))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, JsonError> {
+        match self.peek() {
+            Some(b'{') => self.nested(Parser::parse_object),
+            Some(b'[') => self.nested(Parser::parse_array),
+            Some(b'"') => self.parse_string().map(Json::String),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            _ => Err(self.syntax("a value")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Json, JsonError>,
+    ) -> Result<Json, JsonError> {
+        if self.depth == MAX_DEPTH {
+            return Err(JsonError::TooDeep {
+                offset: self.offset,
+            });
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn parse_object(&mut self) -> Result<Json, JsonError> {
+        self.expect(b'{', "'{'")?;
+        let mut members = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.offset += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.syntax("a key"));
+            }
+            let key = self.parse_string()?;
+            self.expect(b':', "':'")?;
+            members.push((key, self.parse_value()?));
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b'}') => {
+                    self.offset += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.syntax("',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, JsonError> {
+        self.expect(b'[', "'['")?;
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.offset += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b']') => {
+                    self.offset += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.syntax("',' or ']'")),
+            }
+        }
+    }
+
+    // skips ASCII digits, returns how many
+    fn skip_digits(&mut self) -> usize {
+        let start = self.offset;
+        while let Some(b'0'..=b'9') = self.bytes.get(self.offset) {
+            self.offset += 1;
+        }
+        self.offset - start
+    }
+
+    // `-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?`, the value is checked
+    // once the type is known
+    fn parse_number(&mut self) -> Result<Json, JsonError> {
+        let start = self.offset;
+        if self.bytes.get(self.offset) == Some(&b'-') {
+            self.offset += 1;
+        }
+        match self.bytes.get(self.offset) {
+            Some(b'0') => self.offset += 1,
+            Some(b'1'..=b'9') => {
+                self.skip_digits();
+            }
+            _ => return Err(self.syntax("a digit")),
+        }
+        if self.bytes.get(self.offset) == Some(&b'.') {
+            self.offset += 1;
+            if self.skip_digits() == 0 {
+                return Err(self.syntax("a digit"));
+            }
+        }
+        if let Some(b'e' | b'E') = self.bytes.get(self.offset) {
+            self.offset += 1;
+            if let Some(b'+' | b'-') = self.bytes.get(self.offset) {
+                self.offset += 1;
+            }
+            if self.skip_digits() == 0 {
+                return Err(self.syntax("a digit"));
+            }
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.offset]).expect("ascii");
+        Ok(Json::Number(text.to_string()))
+    }
+
+    fn parse_hex(&mut self) -> Result<u32, JsonError> {
+        let digits = self
+            .bytes
+            .get(self.offset..self.offset + 4)
+            .filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.syntax("4 hex digits"))?;
+        self.offset += 4;
+        Ok(digits)
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"', "'\"'")?;
+        let mut out = String::new();
+        loop {
+            let start = self.offset;
+            while let Some(b) = self.bytes.get(self.offset) {
+                if *b == b'"' || *b == b'\\' || *b < 0x20 {
+                    break;
+                }
+                self.offset += 1;
+            }
+            // the input is a `str`, the run ends on an ASCII byte
+            out.push_str(std::str::from_utf8(&self.bytes[start..self.offset]).expect("utf8"));
+            match self.bytes.get(self.offset) {
+                Some(b'"') => {
+                    self.offset += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => self.offset += 1,
+                _ => return Err(self.syntax("'\"'")),
+            }
+            let escape = self.bytes.get(self.offset).copied();
+            self.offset += 1;
+            match escape {
+                Some(b'"') => out.push('"'),
+                Some(b'\\') => out.push('\\'),
+                Some(b'/') => out.push('/'),
+                Some(b'b') => out.push('\u{8}'),
+                Some(b'f') => out.push('\u{c}'),
+                Some(b'n') => out.push('\n'),
+                Some(b'r') => out.push('\r'),
+                Some(b't') => out.push('\t'),
+                Some(b'u') => {
+                    let mut code = self.parse_hex()?;
+                    // a high surrogate has to be followed by a low one
+                    if (0xd800..0xdc00).contains(&code) {
+                        if !self.bytes[self.offset..].starts_with(b"\\u") {
+                            return Err(self.syntax("a low surrogate"));
+                        }
+                        self.offset += 2;
+                        let low = self.parse_hex()?;
+                        if !(0xdc00..=0xdfff).contains(&low) {
+                            self.offset -= 6;
+                            return Err(self.syntax("a low surrogate"));
+                        }
+                        code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                    }
+                    out.push(char::from_u32(code).ok_or_else(|| self.syntax("a unicode scalar"))?);
+                }
+                _ => {
+                    self.offset -= 1;
+                    return Err(self.syntax("an escape sequence"));
+                }
+            }
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn hash_from_json(json: &Json, path: &str) -> Result<Hash, JsonError> {
+    let members = match json {
+        Json::Object(members) => members,
+        _ => {
+            return Err(JsonError::InvalidValue {
+                type_: "HASH".to_string(),
+                path: path.to_string(),
+            })
+        }
+    };
+    let mut hash = Hash::new();
+    for (key, node) in members {
+        let path = join(path, key);
+        let value = typed_from_json(node, &path)?;
+        let mut attrs = Attributes::new();
+        if let Some(json_attrs) = node.get("attrs") {
+            let members = match json_attrs {
+                Json::Object(members) => members,
+                _ => {
+                    return Err(JsonError::InvalidValue {
+                        type_: "attributes".to_string(),
+                        path,
+                    })
+                }
+            };
+            for (attr, attr_node) in members {
+                let attr_path = format!("{path}@{attr}");
+                attrs.insert(attr, typed_from_json(attr_node, &attr_path)?);
+            }
+        }
+        hash.insert_index_attrs(key, value, attrs);
+    }
+    Ok(hash)
+}
+
+fn int<T: FromStr>(json: &Json) -> Option<T> {
+    match json {
+        Json::Number(text) | Json::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+fn float<T: FromStr>(json: &Json) -> Option<T> {
+    match json {
+        Json::Number(text) => text.parse().ok(),
+        Json::String(text) => match text.as_str() {
+            "NaN" | "Infinity" | "-Infinity" => text.parse().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn boolean(json: &Json) -> Option<bool> {
+    match json {
+        Json::Bool(x) => Some(*x),
+        _ => None,
+    }
+}
+
+fn character(json: &Json) -> Option<char> {
+    match json {
+        Json::String(text) => {
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(c),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn string(json: &Json) -> Option<String> {
+    match json {
+        Json::String(text) => Some(text.clone()),
+        _ => None,
+    }
+}
+
+fn complex<T: FromStr>(json: &Json) -> Option<Complex<T>> {
+    match json {
+        Json::Array(items) if items.len() == 2 => {
+            Some(Complex::new(float(&items[0])?, float(&items[1])?))
+        }
+        _ => None,
+    }
+}
+
+fn vector<T>(json: &Json, element: impl Fn(&Json) -> Option<T>) -> Option<Vec<T>> {
+    match json {
+        Json::Array(items) => items.iter().map(element).collect(),
+        _ => None,
+    }
+}
+
+fn typed_from_json(node: &Json, path: &str) -> Result<HashValue, JsonError> {
+    let invalid = |type_: &str| JsonError::InvalidValue {
+        type_: type_.to_string(),
+        path: path.to_string(),
+    };
+    let type_ = match node.get("type") {
+        Some(Json::String(type_)) => type_.as_str(),
+        _ => return Err(invalid("typed node")),
+    };
+    let json = node.get("value").ok_or_else(|| invalid(type_))?;
+    let value = match type_ {
+        "BOOL" => boolean(json).map(HashValue::Bool),
+        "VECTOR_BOOL" => vector(json, boolean).map(HashValue::VectorBool),
+        "CHAR" => character(json).map(HashValue::Char),
+        "VECTOR_CHAR" => vector(json, character).map(HashValue::VectorChar),
+        "INT8" => int(json).map(HashValue::Int8),
+        "VECTOR_INT8" => vector(json, int).map(HashValue::VectorInt8),
+        "UINT8" => int(json).map(HashValue::UInt8),
+        "VECTOR_UINT8" => vector(json, int).map(HashValue::VectorUInt8),
+        "INT16" => int(json).map(HashValue::Int16),
+        "VECTOR_INT16" => vector(json, int).map(HashValue::VectorInt16),
+        "UINT16" => int(json).map(HashValue::UInt16),
+        "VECTOR_UINT16" => vector(json, int).map(HashValue::VectorUInt16),
+        "INT32" => int(json).map(HashValue::Int32),
+        "VECTOR_INT32" => vector(json, int).map(HashValue::VectorInt32),
+        "UINT32" => int(json).map(HashValue::UInt32),
+        "VECTOR_UINT32" => vector(json, int).map(HashValue::VectorUInt32),
+        "INT64" => int(json).map(HashValue::Int64),
+        "VECTOR_INT64" => vector(json, int).map(HashValue::VectorInt64),
+        "UINT64" => int(json).map(HashValue::UInt64),
+        "VECTOR_UINT64" => vector(json, int).map(HashValue::VectorUInt64),
+        "FLOAT" => float(json).map(HashValue::Float32),
+        "VECTOR_FLOAT" => vector(json, float).map(HashValue::VectorFloat32),
+        "DOUBLE" => float(json).map(HashValue::Float64),
+        "VECTOR_DOUBLE" => vector(json, float).map(HashValue::VectorFloat64),
+        "COMPLEX_FLOAT" => complex(json).map(HashValue::ComplexFloat32),
+        "VECTOR_COMPLEX_FLOAT" => vector(json, complex).map(HashValue::VectorComplexFloat32),
+        "COMPLEX_DOUBLE" => complex(json).map(HashValue::ComplexFloat64),
+        "VECTOR_COMPLEX_DOUBLE" => vector(json, complex).map(HashValue::VectorComplexFloat64),
+        "STRING" => string(json).map(HashValue::String),
+        "VECTOR_STRING" => vector(json, string).map(HashValue::VectorString),
+        "HASH" => return hash_from_json(json, path).map(HashValue::Hash),
+        "VECTOR_HASH" => {
+            let rows = match json {
+                Json::Array(rows) => rows,
+                _ => return Err(invalid(type_)),
+            };
+            let rows = rows
+                .iter()
+                .enumerate()
+                .map(|(i, row)| hash_from_json(row, &format!("{path}[{i}]")))
+                .collect::<Result<_, _>>()?;
+            Some(HashValue::VectorHash(rows))
+        }
+        "SCHEMA" => match (json.get("classId"), json.get("hash")) {
+            (Some(Json::String(class_id)), Some(hash)) => {
+                let hash = hash_from_json(hash, path)?;
+                Some(HashValue::Schema(Schema::new(class_id.clone(), hash)))
+            }
+            _ => None,
+        },
+        "NONE" => matches!(json, Json::Null).then_some(HashValue::None),
+        "VECTOR_NONE" => match json {
+            Json::Array(items) if items.iter().all(|item| matches!(item, Json::Null)) => {
+                Some(HashValue::VectorNone(items.len()))
+            }
+            _ => None,
+        },
+        "BYTE_ARRAY" => match json {
+            Json::String(text) => base64_decode(text).map(HashValue::ByteArray),
+            _ => None,
+        },
+        _ => {
+            return Err(JsonError::UnknownType {
+                type_: type_.to_string(),
+                path: path.to_string(),
+            })
+        }
+    };
+    value.ok_or_else(|| invalid(type_))
+}
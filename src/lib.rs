@@ -1,11 +1,27 @@
+// lets the code generated by `karabo_derive` refer to `::karabo_wasm` here too
+extern crate self as karabo_wasm;
+
 pub mod attributes;
 pub mod binary_readers;
 pub mod binary_writers;
+mod bulk;
+pub mod convert;
+pub mod frame_decoder;
 pub mod hash;
+#[cfg(feature = "serde")]
+pub mod hash_deserializer;
+pub mod hash_diff;
+#[cfg(feature = "serde")]
+pub mod hash_serializer;
+pub mod hash_view;
+pub mod json;
 pub mod schema;
 pub mod types;
 pub mod web_socket;
 
+#[cfg(feature = "derive")]
+pub use karabo_derive::KaraboHash;
+
 #[cfg(test)]
 mod tests;
 
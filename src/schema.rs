@@ -1,4 +1,6 @@
+use crate::attributes::Attributes;
 use crate::hash::Hash;
+use crate::types::{Complex, HashValue};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Schema {
@@ -11,3 +13,83 @@ impl Schema {
         Schema { class_id, hash }
     }
 }
+
+// Karabo `accessMode` attribute values
+pub const INIT: i32 = 1;
+pub const READ: i32 = 2;
+pub const WRITE: i32 = 4;
+
+// Types that can be a property of the schema of `#[derive(KaraboHash)]`
+pub trait SchemaType {
+    // Karabo `valueType` of a leaf
+    const VALUE_TYPE: &'static str = "";
+
+    // the schema of the children of a node, `None` for a leaf
+    fn node_schema() -> Option<Hash> {
+        None
+    }
+}
+
+macro_rules! schema_type {
+    ($($t:ty => $name:literal),* $(,)?) => {$(
+        impl SchemaType for $t {
+            const VALUE_TYPE: &'static str = $name;
+        }
+    )*};
+}
+
+schema_type!(
+    bool => "BOOL",
+    Vec<bool> => "VECTOR_BOOL",
+    char => "CHAR",
+    Vec<char> => "VECTOR_CHAR",
+    i8 => "INT8",
+    Vec<i8> => "VECTOR_INT8",
+    u8 => "UINT8",
+    Vec<u8> => "VECTOR_UINT8",
+    i16 => "INT16",
+    Vec<i16> => "VECTOR_INT16",
+    u16 => "UINT16",
+    Vec<u16> => "VECTOR_UINT16",
+    i32 => "INT32",
+    Vec<i32> => "VECTOR_INT32",
+    u32 => "UINT32",
+    Vec<u32> => "VECTOR_UINT32",
+    i64 => "INT64",
+    Vec<i64> => "VECTOR_INT64",
+    u64 => "UINT64",
+    Vec<u64> => "VECTOR_UINT64",
+    f32 => "FLOAT",
+    Vec<f32> => "VECTOR_FLOAT",
+    f64 => "DOUBLE",
+    Vec<f64> => "VECTOR_DOUBLE",
+    Complex<f32> => "COMPLEX_FLOAT",
+    Vec<Complex<f32>> => "VECTOR_COMPLEX_FLOAT",
+    Complex<f64> => "COMPLEX_DOUBLE",
+    Vec<Complex<f64>> => "VECTOR_COMPLEX_DOUBLE",
+    String => "STRING",
+    Vec<String> => "VECTOR_STRING",
+);
+
+// Adds the property `key` of type `T` to `schema`, used by the derive.
+// `attrs` holds the attributes given on the field, e.g. `displayedName`.
+// Panics if `Hash::insert` refuses `key`.
+#[doc(hidden)]
+pub fn add_schema_property<T: SchemaType>(schema: &mut Hash, key: &str, mut attrs: Attributes) {
+    match T::node_schema() {
+        Some(node) => {
+            attrs.insert("nodeType", HashValue::Int32(1));
+            if !schema.insert_attrs(key, HashValue::Hash(node), attrs) {
+                panic!("invalid property key {}", key);
+            }
+        }
+        None => {
+            attrs.insert("nodeType", HashValue::Int32(0));
+            attrs.insert("leafType", HashValue::Int32(0));
+            attrs.insert("valueType", HashValue::String(T::VALUE_TYPE.to_string()));
+            if !schema.insert_attrs(key, HashValue::Int32(0), attrs) {
+                panic!("invalid property key {}", key);
+            }
+        }
+    }
+}
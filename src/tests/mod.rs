@@ -1,12 +1,20 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::attributes::Attributes;
-    use crate::binary_readers::read_hash;
-    use crate::binary_writers::write_hash;
-    use crate::hash::Hash;
-    use crate::types::HashValue;
+    use crate::binary_readers::{
+        read_hash, read_hash_selected, read_hash_with, DecodeError, DecodeOptions,
+    };
+    use crate::binary_writers::{serialized_len, write_hash, EncodeError};
+    use crate::frame_decoder::FrameDecoder;
+    use crate::hash::{Hash, MergePolicy};
+    use crate::hash_diff::{AttributeChange, ValueChange};
+    use crate::hash_view::{HashView, ValueView};
+    use crate::json::{JsonError, JsonMode};
+    use crate::schema::Schema;
+    use crate::types::{Complex, HashValue, TypeError};
     use std::fs::File;
-    use std::io::{BufReader, Cursor, Seek};
+    use std::io::{BufReader, Cursor, Seek, Write};
 
     #[test]
     fn read_from_file() {
@@ -92,10 +100,10 @@ mod tests {
     fn test_hash_round_chars() {
         let mut hash = Hash::new();
         assert!(hash.is_empty());
-        hash.insert("char", HashValue::Char('a'));
+        assert!(hash.insert("char", HashValue::Char('a')));
         let vc = br#"abcdefghijklmno"#.to_vec().iter().map(|&e| e as char).collect();
-        hash.insert("vchar", HashValue::VectorChar(vc));
-        hash.insert("bool", HashValue::Bool(false));
+        assert!(hash.insert("vchar", HashValue::VectorChar(vc)));
+        assert!(hash.insert("bool", HashValue::Bool(false)));
         assert_eq!(hash.keys().len(), 3);
 
         let mut stream = Cursor::new(Vec::new());
@@ -116,34 +124,34 @@ mod tests {
     fn test_equal() {
         // same key different type
         let mut hash = Hash::new();
-        hash.insert("a", HashValue::Char('a'));
+        assert!(hash.insert("a", HashValue::Char('a')));
         let mut hash2 = Hash::new();
         assert!(hash != hash2);
-        hash2.insert("a", HashValue::UInt16(42));
+        assert!(hash2.insert("a", HashValue::UInt16(42)));
         assert!(hash != hash2);
         // same key same type different attribute
         let mut hash = Hash::new();
         let mut attrs = Attributes::new();
         attrs.insert("attr1", HashValue::UInt32(0));
-        hash.insert_attrs("a", HashValue::Char('a'), attrs);
+        assert!(hash.insert_attrs("a", HashValue::Char('a'), attrs));
         let mut hash2 = Hash::new();
         let mut attrs2 = Attributes::new();
         attrs2.insert("attr2", HashValue::UInt32(0));
-        hash2.insert_attrs("a", HashValue::Char('a'), attrs2);
+        assert!(hash2.insert_attrs("a", HashValue::Char('a'), attrs2));
         assert!(hash != hash2);
         let mut hash2 = Hash::new();
-        hash2.insert("a", HashValue::Char('a'));
+        assert!(hash2.insert("a", HashValue::Char('a')));
         assert!(hash != hash2);
         // same key same type same attribute
         // different attribute type
         let mut hash = Hash::new();
         let mut attrs = Attributes::new();
         attrs.insert("attr1", HashValue::UInt32(0));
-        hash.insert_attrs("a", HashValue::Char('a'), attrs);
+        assert!(hash.insert_attrs("a", HashValue::Char('a'), attrs));
         let mut hash2 = Hash::new();
         let mut attrs2 = Attributes::new();
         attrs2.insert("attr1", HashValue::UInt16(0));
-        hash2.insert_attrs("a", HashValue::Char('a'), attrs2);
+        assert!(hash2.insert_attrs("a", HashValue::Char('a'), attrs2));
         assert!(hash != hash2);
     }
 
@@ -151,11 +159,11 @@ mod tests {
     fn test_hash_getter_setter() {
         let mut hash = Hash::new();
         assert!(hash.is_empty());
-        hash.insert("a", HashValue::UInt8(10));
+        assert!(hash.insert("a", HashValue::UInt8(10)));
         let val = hash["a"].as_u8().unwrap();
         assert_eq!(val, 10u8);
         assert_eq!(hash.len(), 1);
-        hash.insert("a", HashValue::Int8(-10));
+        assert!(hash.insert("a", HashValue::Int8(-10)));
         let val = hash["a"].as_i8().unwrap();
         assert_eq!(val, -10i8);
         assert_eq!(hash.len(), 1);
@@ -167,4 +175,1740 @@ mod tests {
         let hash = Hash::new();
         let _ = hash["missing_key"];
     }
+
+    #[test]
+    fn test_decode_truncated() {
+        let data = std::fs::read("./file.bin").unwrap();
+        let mut read_buf = Cursor::new(&data[..100]);
+        let err = read_hash(&mut read_buf).unwrap_err();
+        assert!(matches!(err, DecodeError::Truncated { .. }));
+        assert!(err.offset() <= 100);
+        assert!(!err.path().is_empty());
+
+        let mut read_buf = Cursor::new(&data[..2]);
+        let err = read_hash(&mut read_buf).unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::Truncated {
+                offset: 0,
+                path: String::new()
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut hash = Hash::new();
+        let mut node = Hash::new();
+        assert!(node.insert("a", HashValue::String("text".to_string())));
+        assert!(hash.insert("node", HashValue::Hash(node)));
+        let mut stream = Cursor::new(Vec::new());
+        write_hash(&mut stream, &hash).unwrap();
+        let vec = stream.into_inner();
+
+        // type code of "node.a" is at 4 + 5 + 4 + 4 + 4 + 2
+        let mut data = vec.clone();
+        data[23] = 99;
+        let err = read_hash(&mut Cursor::new(&data)).unwrap_err();
+        assert!(matches!(err, DecodeError::UnknownType { type_: 99, .. }));
+        assert_eq!(err.path(), "node.a");
+
+        // the string content starts after its 4 bytes length
+        let mut data = vec.clone();
+        data[35] = 0xff;
+        let err = read_hash(&mut Cursor::new(&data)).unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::InvalidUtf8 {
+                offset: 35,
+                path: "node.a".to_string()
+            }
+        );
+
+        let mut data = vec.clone();
+        data[31..35].copy_from_slice(&100u32.to_le_bytes());
+        let err = read_hash(&mut Cursor::new(&data)).unwrap_err();
+        assert!(matches!(err, DecodeError::Truncated { offset: 35, .. }));
+        assert_eq!(
+            format!("{err}"),
+            "input truncated at offset 35 (key 'node.a')"
+        );
+    }
+
+    #[test]
+    fn test_hash_round_complex() {
+        let mut hash = Hash::new();
+        assert!(hash.insert("cf32", HashValue::ComplexFloat32(Complex::new(1.5, -2.0))));
+        assert!(hash.insert(
+            "vcf32",
+            HashValue::VectorComplexFloat32(vec![Complex::new(1.0, 2.0), Complex::new(3.0, 4.0)]),
+        ));
+        assert!(hash.insert("cf64", HashValue::ComplexFloat64(Complex::new(0.25, 8.0))));
+        assert!(hash.insert(
+            "vcf64",
+            HashValue::VectorComplexFloat64(vec![Complex::new(-1.0, 0.5)]),
+        ));
+
+        let mut stream = Cursor::new(Vec::new());
+        let size = write_hash(&mut stream, &hash).unwrap();
+        let vec = stream.into_inner();
+        assert_eq!(size, vec.len());
+        // key "cf32", type 24, no attributes, then real and imaginary parts
+        let mut expected = vec![4, 0, 0, 0, 4];
+        expected.extend_from_slice(b"cf32");
+        expected.extend_from_slice(&24u32.to_le_bytes());
+        expected.extend_from_slice(&0u32.to_le_bytes());
+        expected.extend_from_slice(&1.5f32.to_le_bytes());
+        expected.extend_from_slice(&(-2.0f32).to_le_bytes());
+        assert_eq!(&vec[..expected.len()], &expected[..]);
+
+        let read_hash = read_hash(&mut Cursor::new(&vec)).unwrap();
+        assert_eq!(read_hash, hash);
+        let value = read_hash["cf32"].as_complex_f32().unwrap();
+        assert_eq!(value, Complex::new(1.5, -2.0));
+        let value = read_hash["cf64"].as_complex_f64().unwrap();
+        assert_eq!(value.im, 8.0);
+        let value = read_hash["vcf32"].as_vector_complex_f32().unwrap();
+        assert_eq!(value.len(), 2);
+        let value = read_hash["vcf64"].as_vector_complex_f64().unwrap();
+        assert_eq!(value[0], Complex::new(-1.0, 0.5));
+        assert_eq!(format!("{}", read_hash["cf32"]), "COMPLEX_FLOAT (1.5,-2)");
+        assert_eq!(
+            format!("{}", read_hash["vcf32"]),
+            "VECTOR_COMPLEX_FLOAT [(1,2), (3,4)]"
+        );
+    }
+
+    #[test]
+    fn test_hash_round_none_bytes() {
+        let mut hash = Hash::new();
+        assert!(hash.insert("none", HashValue::None));
+        assert!(hash.insert("vnone", HashValue::VectorNone(2)));
+        assert!(hash.insert("bytes", HashValue::ByteArray(b"\x00raw\xffblob".to_vec())));
+        let mut attrs = Attributes::new();
+        attrs.insert("placeholder", HashValue::None);
+        assert!(hash.insert_attrs("empty", HashValue::ByteArray(Vec::new()), attrs));
+
+        let mut stream = Cursor::new(Vec::new());
+        let size = write_hash(&mut stream, &hash).unwrap();
+        let vec = stream.into_inner();
+        assert_eq!(size, vec.len());
+        // key "none", type 34, no attributes and a 4 bytes placeholder
+        let mut expected = vec![4, 0, 0, 0, 4];
+        expected.extend_from_slice(b"none");
+        expected.extend_from_slice(&34u32.to_le_bytes());
+        expected.extend_from_slice(&0u32.to_le_bytes());
+        expected.extend_from_slice(&0u32.to_le_bytes());
+        assert_eq!(&vec[..expected.len()], &expected[..]);
+
+        let read_hash = read_hash(&mut Cursor::new(&vec)).unwrap();
+        assert_eq!(read_hash, hash);
+        assert!(read_hash["none"].is_none());
+        assert_eq!(read_hash["vnone"], HashValue::VectorNone(2));
+        assert_eq!(read_hash["bytes"].as_bytes().unwrap(), b"\x00raw\xffblob");
+        assert!(read_hash
+            .get_attributes("empty")
+            .unwrap()
+            .get("placeholder")
+            .unwrap()
+            .is_none());
+        assert_eq!(format!("{}", read_hash["bytes"]), "BYTE_ARRAY 9 bytes");
+    }
+
+    struct FullWriter {
+        capacity: usize,
+    }
+
+    impl Write for FullWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let size = buf.len().min(self.capacity);
+            self.capacity -= size;
+            Ok(size)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let mut hash = Hash::new();
+        let key = "k".repeat(256);
+        assert!(hash.insert(&key, HashValue::Bool(true)));
+        let mut stream = Cursor::new(Vec::new());
+        let err = write_hash(&mut stream, &hash).unwrap_err();
+        assert!(matches!(err, EncodeError::KeyTooLong { key: k } if k == key));
+
+        let mut hash = Hash::new();
+        let mut attrs = Attributes::new();
+        attrs.insert(&key, HashValue::Bool(true));
+        assert!(hash.insert_attrs("a", HashValue::Bool(true), attrs));
+        let err = write_hash(&mut stream, &hash).unwrap_err();
+        assert!(matches!(err, EncodeError::KeyTooLong { .. }));
+
+        // errors deep in the tree are found before anything is written
+        let mut hash = Hash::new();
+        assert!(hash.insert("a", HashValue::String("first".to_string())));
+        assert!(hash.insert(&format!("b.c.{key}"), HashValue::Bool(true)));
+        let mut stream = Cursor::new(Vec::new());
+        let err = write_hash(&mut stream, &hash).unwrap_err();
+        assert!(matches!(err, EncodeError::KeyTooLong { key: k } if k == key));
+        assert!(stream.get_ref().is_empty());
+        #[cfg(target_pointer_width = "64")]
+        {
+            let mut hash = Hash::new();
+            assert!(hash.insert("a", HashValue::Int32(1)));
+            assert!(hash.insert("b.c", HashValue::VectorNone(1 << 32)));
+            let err = write_hash(&mut stream, &hash).unwrap_err();
+            assert!(matches!(err, EncodeError::CollectionTooLarge { .. }));
+            assert!(stream.get_ref().is_empty());
+        }
+
+        // a key of 255 bytes is fine
+        let mut hash = Hash::new();
+        assert!(hash.insert(&"k".repeat(255), HashValue::String("value".to_string())));
+        let mut stream = Cursor::new(Vec::new());
+        let size = write_hash(&mut stream, &hash).unwrap();
+        let read_back = read_hash(&mut Cursor::new(stream.into_inner())).unwrap();
+        assert_eq!(read_back, hash);
+
+        // short writes are reported instead of being dropped
+        let mut writer = FullWriter { capacity: size - 1 };
+        let err = write_hash(&mut writer, &hash).unwrap_err();
+        assert!(matches!(err, EncodeError::Io(ref e) if e.kind() == std::io::ErrorKind::WriteZero));
+        let mut writer = FullWriter { capacity: size };
+        assert_eq!(write_hash(&mut writer, &hash).unwrap(), size);
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        let mut inner = Hash::new();
+        assert!(inner.insert("s", HashValue::String("0123456789".to_string())));
+        assert!(inner.insert("v", HashValue::VectorUInt32(vec![1, 2, 3, 4])));
+        let mut node = Hash::new();
+        assert!(node.insert("inner", HashValue::Hash(inner)));
+        let mut hash = Hash::new();
+        assert!(hash.insert("node", HashValue::Hash(node)));
+        let mut stream = Cursor::new(Vec::new());
+        let size = write_hash(&mut stream, &hash).unwrap();
+        let vec = stream.into_inner();
+
+        let opts = DecodeOptions {
+            max_depth: 3,
+            max_vector_len: 4,
+            max_string_len: 10,
+            max_total_bytes: size,
+        };
+        let read_back = read_hash_with(&mut Cursor::new(&vec), &opts).unwrap();
+        assert_eq!(read_back, hash);
+
+        let err = read_hash_with(
+            &mut Cursor::new(&vec),
+            &DecodeOptions {
+                max_depth: 2,
+                ..opts
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, DecodeError::TooDeep { depth: 2, .. }));
+        assert_eq!(err.path(), "node.inner");
+
+        let err = read_hash_with(
+            &mut Cursor::new(&vec),
+            &DecodeOptions {
+                max_string_len: 9,
+                ..opts
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::OversizedLength { length: 10, .. }
+        ));
+        assert_eq!(err.path(), "node.inner.s");
+
+        let err = read_hash_with(
+            &mut Cursor::new(&vec),
+            &DecodeOptions {
+                max_vector_len: 3,
+                ..opts
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::OversizedLength { length: 4, .. }
+        ));
+        assert_eq!(err.path(), "node.inner.v");
+
+        let err = read_hash_with(
+            &mut Cursor::new(&vec),
+            &DecodeOptions {
+                max_total_bytes: size - 1,
+                ..opts
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, DecodeError::OversizedLength { .. }));
+
+        // a corrupt length is rejected before allocating
+        let mut data = vec.clone();
+        let offset = data.len() - 16 - 4;
+        data[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = read_hash(&mut Cursor::new(&data)).unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::OversizedLength {
+                length: u64::from(u32::MAX),
+                offset,
+                path: "node.inner.v".to_string()
+            }
+        );
+    }
+
+    fn frame(hash: &Hash) -> Vec<u8> {
+        let mut stream = Cursor::new(Vec::new());
+        let size = write_hash(&mut stream, hash).unwrap() as u32;
+        let mut frame = size.to_le_bytes().to_vec();
+        frame.extend(stream.into_inner());
+        frame
+    }
+
+    #[test]
+    fn test_frame_decoder() {
+        let mut first = Hash::new();
+        assert!(first.insert("a", HashValue::Int32(1)));
+        let mut second = Hash::new();
+        assert!(second.insert("b", HashValue::String("two".to_string())));
+        let mut data = frame(&first);
+        data.extend(frame(&second));
+        data.extend(frame(&first));
+
+        // byte by byte
+        let mut decoder = FrameDecoder::new();
+        let mut hashes = Vec::new();
+        for byte in &data {
+            decoder.push(&[*byte]);
+            while let Some(hash) = decoder.next_frame().unwrap() {
+                hashes.push(hash);
+            }
+        }
+        assert_eq!(hashes, vec![first.clone(), second.clone(), first.clone()]);
+        assert_eq!(decoder.buffered(), 0);
+
+        // several frames and a partial one in a single chunk
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&data[..data.len() - 3]);
+        assert_eq!(decoder.next_frame().unwrap(), Some(first.clone()));
+        assert_eq!(decoder.next_frame().unwrap(), Some(second));
+        assert_eq!(decoder.next_frame().unwrap(), None);
+        decoder.push(&data[data.len() - 3..]);
+        assert_eq!(decoder.next_frame().unwrap(), Some(first.clone()));
+        assert_eq!(decoder.next_frame().unwrap(), None);
+    }
+
+    #[test]
+    fn test_frame_decoder_length_mismatch() {
+        let mut first = Hash::new();
+        assert!(first.insert("a", HashValue::Int32(1)));
+        let mut data = frame(&first);
+        // announce two bytes more than the hash contains
+        let size = data.len() as u32 - 4 + 2;
+        data[..4].copy_from_slice(&size.to_le_bytes());
+        data.extend([0, 0]);
+        data.extend(frame(&first));
+
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&data);
+        let err = decoder.next_frame().unwrap_err();
+        assert!(matches!(err, DecodeError::LengthMismatch { .. }));
+        assert_eq!(err.offset(), size as usize - 2);
+        // the next frame is still decoded
+        assert_eq!(decoder.next_frame().unwrap(), Some(first));
+    }
+
+    #[test]
+    fn test_frame_decoder_oversized_length() {
+        let mut first = Hash::new();
+        assert!(first.insert("a", HashValue::Int32(1)));
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&u32::MAX.to_le_bytes());
+        let err = decoder.next_frame().unwrap_err();
+        assert!(matches!(err, DecodeError::OversizedLength { .. }));
+        assert!(decoder.is_failed());
+
+        // valid frames are not swallowed, the decoder stays failed
+        decoder.push(&frame(&first));
+        assert_eq!(decoder.next_frame(), Err(err.clone()));
+        assert_eq!(decoder.next_frame(), Err(err));
+
+        decoder.reset();
+        assert!(!decoder.is_failed());
+        assert_eq!(decoder.next_frame().unwrap(), None);
+        decoder.push(&frame(&first));
+        assert_eq!(decoder.next_frame().unwrap(), Some(first));
+    }
+
+    #[test]
+    fn test_hash_view() {
+        let data = std::fs::read("./file.bin").unwrap();
+        let hash = read_hash(&mut Cursor::new(&data)).unwrap();
+        let view = HashView::parse(&data).unwrap();
+        assert_eq!(view.len(), 27);
+        assert_eq!(view.keys(), hash.keys());
+        assert_eq!(view.to_hash(), hash);
+
+        assert_eq!(view.get("node.f64"), Some(&ValueView::Float64(200.0)));
+        assert_eq!(view.get("node.missing"), None);
+        assert_eq!(view.get("i8.nested"), None);
+        // strings point into the input buffer
+        let text = view.get("string").unwrap().as_str().unwrap();
+        assert_eq!(text, "hi");
+        assert!(data.as_ptr_range().contains(&text.as_ptr()));
+        match view.get("node.vf32").unwrap() {
+            ValueView::VectorFloat32(vector) => {
+                assert_eq!(vector.len(), 1);
+                assert_eq!(vector.get(0), Some(12.0));
+                assert_eq!(vector.get(1), None);
+                assert_eq!(vector.get(usize::MAX / 4), None);
+                assert_eq!(vector.get(usize::MAX), None);
+                assert_eq!(vector.to_vec(), vec![12.0]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match view.get("vh").unwrap() {
+            ValueView::VectorHash(rows) => {
+                assert_eq!(rows[0].get("u8"), Some(&ValueView::UInt8(250)))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(view.get_attributes("i8").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_hash_view_errors() {
+        let data = std::fs::read("./file.bin").unwrap();
+        let err = HashView::parse(&data[..100]).unwrap_err();
+        let expected = read_hash(&mut Cursor::new(&data[..100])).unwrap_err();
+        assert_eq!(err, expected);
+
+        let mut extra = data.clone();
+        extra.push(0);
+        let err = HashView::parse(&extra).unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::LengthMismatch {
+                length: extra.len() as u64,
+                offset: data.len(),
+                path: String::new()
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_selected() {
+        let data = std::fs::read("./file.bin").unwrap();
+        let hash = read_hash(&mut Cursor::new(&data)).unwrap();
+        let paths = ["node.f64", "i8", "vh", "schema", "node.vstring.missing"];
+        let mut read_buf = Cursor::new(&data);
+        let selected = read_hash_selected(&mut read_buf, &paths).unwrap();
+        // everything has been consumed
+        assert_eq!(read_buf.position() as usize, data.len());
+        assert_eq!(selected.keys(), vec!["i8", "node", "vh", "schema"]);
+        assert_eq!(selected["i8"], hash["i8"]);
+        assert_eq!(selected["vh"], hash["vh"]);
+        assert_eq!(selected["schema"], hash["schema"]);
+        let node = selected["node"].as_hash().unwrap();
+        assert_eq!(node.keys(), vec!["f64"]);
+        assert_eq!(node["f64"], hash["node.f64"]);
+
+        // a prefix selects the whole subtree
+        let selected = read_hash_selected(&mut Cursor::new(&data), &["node"]).unwrap();
+        assert_eq!(selected.keys(), vec!["node"]);
+        assert_eq!(selected["node"], hash["node"]);
+
+        let selected = read_hash_selected(&mut Cursor::new(&data), &[]).unwrap();
+        assert!(selected.is_empty());
+
+        // errors in skipped values are still reported
+        let err =
+            read_hash_selected(&mut Cursor::new(&data[..data.len() - 10]), &["i8"]).unwrap_err();
+        assert!(matches!(err, DecodeError::Truncated { .. }));
+        assert_eq!(err.path(), "schema");
+    }
+
+    #[test]
+    fn test_serialized_len() {
+        let data = std::fs::read("./file.bin").unwrap();
+        let hash = read_hash(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(serialized_len(&hash), data.len());
+        let value = HashValue::Hash(hash.clone());
+        assert_eq!(serialized_len(&value), data.len());
+
+        // the schema is written without an intermediate buffer
+        let mut buf = Vec::with_capacity(serialized_len(&hash));
+        let size = write_hash(&mut buf, &hash).unwrap();
+        assert_eq!(size, data.len());
+        assert_eq!(buf, data);
+        assert_eq!(buf.capacity(), data.len());
+
+        let mut hash = Hash::new();
+        assert!(hash.insert("cf64", HashValue::ComplexFloat64(Complex::new(1.0, 2.0))));
+        assert!(hash.insert(
+            "vcf32",
+            HashValue::VectorComplexFloat32(vec![Complex::new(1.0, 2.0)]),
+        ));
+        assert!(hash.insert("vnone", HashValue::VectorNone(3)));
+        assert!(hash.insert("bytes", HashValue::ByteArray(vec![1, 2, 3])));
+        assert!(hash.insert("none", HashValue::None));
+        let mut buf = Vec::new();
+        let size = write_hash(&mut buf, &hash).unwrap();
+        assert_eq!(serialized_len(&hash), size);
+    }
+
+    #[test]
+    fn test_bulk_vectors() {
+        let floats: Vec<f32> = (0..100_000).map(|i| i as f32 * 0.5 - 10.0).collect();
+        let longs: Vec<i64> = (0..1000).map(|i| i64::MIN + i * 7).collect();
+        let shorts: Vec<u16> = (0..1000).map(|i| i * 61).collect();
+        let complex: Vec<Complex<f64>> = (0..10).map(|i| Complex::new(i as f64, -1.0)).collect();
+        let mut hash = Hash::new();
+        assert!(hash.insert("vf32", HashValue::VectorFloat32(floats.clone())));
+        assert!(hash.insert("vi64", HashValue::VectorInt64(longs.clone())));
+        assert!(hash.insert("vu16", HashValue::VectorUInt16(shorts.clone())));
+        assert!(hash.insert("vcf64", HashValue::VectorComplexFloat64(complex.clone())));
+        let mut buf = Vec::new();
+        write_hash(&mut buf, &hash).unwrap();
+
+        // same bytes as encoding element by element
+        let mut expected = 4u32.to_le_bytes().to_vec();
+        let header = |expected: &mut Vec<u8>, key: &str, type_: u32, len: usize| {
+            expected.push(key.len() as u8);
+            expected.extend_from_slice(key.as_bytes());
+            expected.extend_from_slice(&type_.to_le_bytes());
+            expected.extend_from_slice(&0u32.to_le_bytes());
+            expected.extend_from_slice(&(len as u32).to_le_bytes());
+        };
+        header(&mut expected, "vf32", 21, floats.len());
+        floats
+            .iter()
+            .for_each(|el| expected.extend(el.to_le_bytes()));
+        header(&mut expected, "vi64", 17, longs.len());
+        longs
+            .iter()
+            .for_each(|el| expected.extend(el.to_le_bytes()));
+        header(&mut expected, "vu16", 11, shorts.len());
+        shorts
+            .iter()
+            .for_each(|el| expected.extend(el.to_le_bytes()));
+        header(&mut expected, "vcf64", 27, complex.len());
+        complex.iter().for_each(|el| {
+            expected.extend(el.re.to_le_bytes());
+            expected.extend(el.im.to_le_bytes());
+        });
+        assert_eq!(buf, expected);
+
+        let read_back = read_hash(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(read_back, hash);
+    }
+
+    #[test]
+    fn test_insert_path() {
+        let mut hash = Hash::new();
+        assert!(hash.insert("a.b.c", HashValue::Int32(1)));
+        assert_eq!(hash.keys(), vec!["a"]);
+        assert_eq!(hash["a.b.c"], HashValue::Int32(1));
+        assert_eq!(hash["a"].as_hash().unwrap().keys(), vec!["b"]);
+
+        let mut attrs = Attributes::new();
+        attrs.insert("unit", HashValue::String("m".to_string()));
+        assert!(hash.insert_attrs("a.b.d", HashValue::Float64(2.0), attrs));
+        assert_eq!(hash["a.b"].as_hash().unwrap().keys(), vec!["c", "d"]);
+        let b = hash["a.b"].as_hash().unwrap();
+        assert_eq!(
+            b.get_attributes("d").unwrap().get("unit"),
+            Some(&HashValue::String("m".to_string()))
+        );
+
+        // a leaf on the way is replaced by a hash, keeping its position
+        assert!(hash.insert("x", HashValue::Bool(true)));
+        assert!(hash.insert("a.b.c.e", HashValue::UInt8(3)));
+        assert_eq!(hash["a.b.c.e"], HashValue::UInt8(3));
+        assert_eq!(hash["a.b"].as_hash().unwrap().keys(), vec!["c", "d"]);
+        assert_eq!(hash.keys(), vec!["a", "x"]);
+
+        // the encoding has nested hashes only
+        let mut buf = Vec::new();
+        write_hash(&mut buf, &hash).unwrap();
+        let read_back = read_hash(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(read_back, hash);
+        assert_eq!(read_back["a.b.d"], HashValue::Float64(2.0));
+    }
+
+    #[test]
+    fn test_path_attributes() {
+        let mut read_buf = BufReader::new(File::open("./file.bin").unwrap());
+        let mut hash = read_hash(&mut read_buf).unwrap();
+        assert!(hash.get_attributes("node.i8").unwrap().is_empty());
+        assert!(hash.get_attributes("node.missing").is_none());
+        assert!(hash.get_attributes("i8.nested").is_none());
+        assert!(hash.get("i8.nested").is_none());
+
+        *hash.get_mut("node.i8").unwrap() = HashValue::Int8(5);
+        assert_eq!(hash["node.i8"], HashValue::Int8(5));
+        assert!(hash.get_mut("node.missing").is_none());
+
+        assert!(hash.set_attribute(
+            "node.f64",
+            "alarmCondition",
+            HashValue::String("none".to_string())
+        ));
+        assert!(!hash.set_attribute("node.missing", "unit", HashValue::Int32(0)));
+        assert_eq!(
+            hash.get_attribute("node.f64", "alarmCondition"),
+            Some(&HashValue::String("none".to_string()))
+        );
+        assert!(hash.get_attribute("node.f64", "missing").is_none());
+        let attrs = hash.get_attributes_mut("node.f64").unwrap();
+        *attrs.get_mut("alarmCondition").unwrap() = HashValue::String("warn".to_string());
+        attrs.insert("sec", HashValue::UInt64(42));
+        let node = hash["node"].as_hash().unwrap();
+        let attrs = node.get_attributes("f64").unwrap();
+        assert_eq!(attrs.keys(), vec!["alarmCondition", "sec"]);
+        assert_eq!(
+            attrs.get("alarmCondition"),
+            Some(&HashValue::String("warn".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_vector_hash_index() {
+        let mut read_buf = BufReader::new(File::open("./file.bin").unwrap());
+        let mut hash = read_hash(&mut read_buf).unwrap();
+        assert_eq!(hash["vh[0].i8"], HashValue::Int8(-1));
+        assert_eq!(hash.get("vh[0].node"), None);
+        assert_eq!(hash.get("vh[1].i8"), None);
+        assert_eq!(hash.get("vh[x].i8"), None);
+        assert_eq!(hash.get("vh[0]"), None);
+        assert_eq!(hash.get_hash("vh[0]").unwrap().len(), 24);
+        assert_eq!(hash.get_hash("node").unwrap().len(), 24);
+        assert!(hash.get_hash("vh[1]").is_none());
+        assert!(hash.get_hash("i8").is_none());
+
+        *hash.get_mut("vh[0].i8").unwrap() = HashValue::Int8(7);
+        assert_eq!(hash["vh[0].i8"], HashValue::Int8(7));
+        assert!(hash.get_mut("vh[3].i8").is_none());
+
+        // a row is appended at the end, indices past it are refused
+        let before = hash.clone();
+        assert!(!hash.insert("vh[2].x", HashValue::UInt32(1)));
+        assert!(!hash.insert("vh[1]", HashValue::Int32(1)));
+        assert!(!hash.set("vh[0]", HashValue::Int32(1)));
+        assert!(!hash.insert("new.rows[1].x", HashValue::Int32(1)));
+        assert!(!hash.insert("vh[0].rows[1].x", HashValue::Int32(1)));
+        assert!(!hash.insert(&format!("vh[{}].x", usize::MAX), HashValue::Int32(1)));
+        assert!(hash.fully_equals(&before));
+        assert!(hash.insert("vh[1].x", HashValue::UInt32(1)));
+        assert_eq!(hash["vh[1].x"], HashValue::UInt32(1));
+        let mut row = Hash::new();
+        assert!(row.insert("y", HashValue::Bool(true)));
+        assert!(hash.insert("vh[2]", HashValue::Hash(row)));
+        assert_eq!(hash["vh[2].y"], HashValue::Bool(true));
+        assert!(hash.insert("table[0].a.b", HashValue::Int16(3)));
+        assert_eq!(hash["table[0].a.b"], HashValue::Int16(3));
+        assert!(matches!(hash["table"], HashValue::VectorHash(ref rows) if rows.len() == 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 3 out of range for vh of length 1 in vh[3].i8")]
+    fn test_vector_hash_out_of_range() {
+        let mut read_buf = BufReader::new(File::open("./file.bin").unwrap());
+        let hash = read_hash(&mut read_buf).unwrap();
+        let _ = hash["vh[3].i8"];
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 5 out of range for vh of length 1 in vh[5]")]
+    fn test_vector_hash_row_out_of_range() {
+        let mut read_buf = BufReader::new(File::open("./file.bin").unwrap());
+        let hash = read_hash(&mut read_buf).unwrap();
+        let _ = hash["vh[5]"];
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut read_buf = BufReader::new(File::open("./file.bin").unwrap());
+        let original = read_hash(&mut read_buf).unwrap();
+        let mut hash = original.clone();
+        let keys = hash.keys();
+
+        let node = hash.remove("i8").unwrap();
+        assert_eq!(node.key, "i8");
+        assert_eq!(node.value, HashValue::Int8(-1));
+        assert!(hash.remove("i8").is_none());
+        assert!(hash.get("i8").is_none());
+        let expected: Vec<String> = keys.iter().filter(|k| *k != "i8").cloned().collect();
+        assert_eq!(hash.keys(), expected);
+        // positions after the removed node are still valid
+        for key in &expected {
+            assert_eq!(hash[key.as_str()], original[key.as_str()]);
+        }
+        assert!(hash.insert("i8", HashValue::Int8(3)));
+        assert_eq!(hash.keys().last().unwrap(), "i8");
+        assert_eq!(hash["i8"], HashValue::Int8(3));
+
+        assert_eq!(
+            hash.remove("node.f64").unwrap().value,
+            HashValue::Float64(200.0)
+        );
+        assert!(hash.get("node.f64").is_none());
+        assert_eq!(
+            hash.remove("vh[0].string").unwrap().value,
+            HashValue::String("hi".into())
+        );
+        assert!(hash.get("vh[0].string").is_none());
+        assert!(hash.remove("vh[0]").is_none());
+        assert!(hash.remove("missing.i8").is_none());
+
+        hash.set_attribute("string", "unit", HashValue::Int32(1));
+        hash.set_attribute("string", "scale", HashValue::Int32(2));
+        assert_eq!(
+            hash.remove_attribute("string", "unit"),
+            Some(HashValue::Int32(1))
+        );
+        assert_eq!(hash.remove_attribute("string", "unit"), None);
+        assert_eq!(hash.remove_attribute("missing", "unit"), None);
+        let attrs = hash.get_attributes_mut("string").unwrap();
+        assert_eq!(attrs.keys(), vec!["scale".to_string()]);
+        assert_eq!(attrs.get("scale"), Some(&HashValue::Int32(2)));
+        attrs.insert("unit", HashValue::Int32(4));
+        attrs.retain(|attr| attr.key != "scale");
+        assert_eq!(attrs.get("unit"), Some(&HashValue::Int32(4)));
+        attrs.clear();
+        assert!(attrs.is_empty() && attrs.get("unit").is_none());
+
+        hash.retain(|node| matches!(node.value, HashValue::Hash(_)));
+        assert_eq!(hash.keys(), vec!["node".to_string()]);
+        assert_eq!(hash.get_hash("node").unwrap().len(), 23);
+        hash.clear();
+        assert!(hash.is_empty() && hash.get("node").is_none());
+    }
+
+    #[test]
+    fn test_iterators() {
+        let mut read_buf = BufReader::new(File::open("./file.bin").unwrap());
+        let mut hash = read_hash(&mut read_buf).unwrap();
+        hash.set_attribute("i8", "unit", HashValue::Int32(1));
+        hash.set_attribute("i8", "scale", HashValue::Int32(2));
+
+        let keys: Vec<&str> = hash.iter().map(|(key, _, _)| key).collect();
+        assert_eq!(keys, hash.keys());
+        assert_eq!(hash.iter().len(), hash.len());
+        assert_eq!(hash.values().count(), hash.len());
+        for ((key, value, attrs), other) in hash.iter().zip(hash.values()) {
+            assert_eq!(value, &hash[key]);
+            assert_eq!(value, other);
+            assert_eq!(attrs, hash.get_attributes(key).unwrap());
+        }
+        let mut nodes = 0;
+        for (_, value, _) in &hash {
+            if let HashValue::Hash(inner) = value {
+                nodes += inner.iter().count();
+            }
+        }
+        assert_eq!(nodes, 24);
+
+        for (key, value, attrs) in hash.iter_mut() {
+            if key == "i8" {
+                *value = HashValue::Int8(5);
+                for (_, attr) in attrs.iter_mut() {
+                    *attr = HashValue::Int32(0);
+                }
+            }
+        }
+        assert_eq!(hash["i8"], HashValue::Int8(5));
+
+        let attrs = hash.get_attributes("i8").unwrap();
+        let attr_keys: Vec<&str> = attrs.iter().map(|(key, _)| key).collect();
+        assert_eq!(attr_keys, vec!["unit", "scale"]);
+        assert!(attrs.values().all(|value| *value == HashValue::Int32(0)));
+        assert_eq!((&attrs.clone()).into_iter().len(), 2);
+        let owned: Vec<String> = attrs.clone().into_iter().map(|attr| attr.key).collect();
+        assert_eq!(owned, attrs.keys());
+    }
+
+    #[test]
+    fn test_paths_flatten() {
+        let mut hash = Hash::new();
+        assert!(hash.insert("motor.position", HashValue::Float64(1.0)));
+        assert!(hash.insert("motor.velocity", HashValue::Float64(2.0)));
+        assert!(hash.insert("empty", HashValue::Hash(Hash::new())));
+        assert!(hash.insert("rows[0].x", HashValue::Int32(1)));
+        assert!(hash.insert("rows[1]", HashValue::Hash(Hash::new())));
+        assert!(hash.insert("rows[2].y.z", HashValue::Int32(2)));
+        assert!(hash.insert("state", HashValue::String("ON".into())));
+        hash.set_attribute("motor.velocity", "unit", HashValue::String("m/s".into()));
+
+        assert_eq!(
+            hash.paths(),
+            vec!["motor.position", "motor.velocity", "empty", "rows", "state"]
+        );
+        assert_eq!(
+            hash.paths_with(true),
+            vec![
+                "motor.position",
+                "motor.velocity",
+                "empty",
+                "rows[0].x",
+                "rows[1]",
+                "rows[2].y.z",
+                "state"
+            ]
+        );
+
+        let mut flat = hash.flatten();
+        assert_eq!(flat.keys(), hash.paths());
+        assert_eq!(flat.unflatten().unwrap(), hash);
+        assert!(flat.get("motor").is_none());
+        let velocity = flat.get_key("motor.velocity").unwrap();
+        assert_eq!(velocity.value, HashValue::Float64(2.0));
+        assert_eq!(
+            velocity.attrs.get("unit"),
+            Some(&HashValue::String("m/s".into()))
+        );
+        assert_eq!(flat.get_key("rows").unwrap().value, hash["rows"]);
+        flat.get_key_mut("state").unwrap().value = HashValue::String("OFF".into());
+        assert_eq!(
+            flat.unflatten().unwrap()["state"],
+            HashValue::String("OFF".into())
+        );
+        assert!(format!("{}", flat).contains("'motor.position' => "));
+        assert!(flat.remove_key("motor.position").is_some());
+        assert!(flat.get_key("motor.position").is_none());
+        assert_eq!(flat.len(), 4);
+
+        let mut read_buf = BufReader::new(File::open("./file.bin").unwrap());
+        let hash = read_hash(&mut read_buf).unwrap();
+        assert_eq!(hash.flatten().len(), 2 * 24 + 2);
+        assert_eq!(hash.flatten().unflatten().unwrap(), hash);
+
+        // literal keys that are not valid paths
+        let mut flat = Hash::new();
+        flat.insert_index_attrs("rows[1].x", HashValue::Int32(1), Attributes::new());
+        assert!(flat.unflatten().is_none());
+        assert!(Hash::diff(&Hash::new(), &flat).to_hash().is_none());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut config = Hash::new();
+        assert!(config.insert("motor.position", HashValue::Float64(1.0)));
+        assert!(config.insert("motor.velocity", HashValue::Float64(2.0)));
+        assert!(config.insert("state", HashValue::String("ON".into())));
+        assert!(config.insert("rows[0].x", HashValue::Int32(1)));
+        assert!(config.insert("rows[1].x", HashValue::Int32(2)));
+        config.set_attribute("motor.position", "unit", HashValue::String("mm".into()));
+        config.set_attribute("motor.position", "alarm", HashValue::Bool(false));
+
+        let mut update = Hash::new();
+        assert!(update.insert("motor.position", HashValue::Float64(5.0)));
+        assert!(update.insert("motor.acceleration", HashValue::Float64(3.0)));
+        assert!(update.insert("rows[0]", HashValue::Hash(Hash::new())));
+        assert!(update.insert("rows[1].y", HashValue::Int32(4)));
+        assert!(update.insert("rows[2].x", HashValue::Int32(5)));
+        assert!(update.insert("new", HashValue::Bool(true)));
+        update.set_attribute("motor.position", "alarm", HashValue::Bool(true));
+
+        let mut merged = config.clone();
+        merged.merge(&update, MergePolicy::MergeAttributes);
+        assert_eq!(merged["motor.position"], HashValue::Float64(5.0));
+        assert_eq!(merged["motor.velocity"], HashValue::Float64(2.0));
+        assert_eq!(merged["motor.acceleration"], HashValue::Float64(3.0));
+        assert_eq!(
+            merged.paths()[..4],
+            [
+                "motor.position",
+                "motor.velocity",
+                "motor.acceleration",
+                "state"
+            ]
+        );
+        assert_eq!(merged["new"], HashValue::Bool(true));
+        assert_eq!(
+            merged.get_attribute("motor.position", "unit"),
+            Some(&HashValue::String("mm".into()))
+        );
+        assert_eq!(
+            merged.get_attribute("motor.position", "alarm"),
+            Some(&HashValue::Bool(true))
+        );
+        // vectors of hashes are replaced
+        assert_eq!(merged["rows"], update["rows"]);
+
+        let mut merged = config.clone();
+        merged.merge(&update, MergePolicy::default());
+        assert!(merged.get_attribute("motor.position", "unit").is_none());
+        assert_eq!(
+            merged.get_attribute("motor.position", "alarm"),
+            Some(&HashValue::Bool(true))
+        );
+
+        let mut merged = config.clone();
+        merged.merge_selected(
+            &update,
+            MergePolicy::MergeAttributes,
+            &[
+                "motor.acceleration",
+                "rows[1].y",
+                "rows[2]",
+                "rows[7]",
+                "missing",
+            ],
+        );
+        assert_eq!(merged["motor.position"], HashValue::Float64(1.0));
+        assert_eq!(merged["motor.acceleration"], HashValue::Float64(3.0));
+        assert!(merged.get("new").is_none());
+        assert_eq!(merged["rows[0].x"], HashValue::Int32(1));
+        assert_eq!(merged["rows[1].x"], HashValue::Int32(2));
+        assert_eq!(merged["rows[1].y"], HashValue::Int32(4));
+        assert_eq!(merged["rows[2].x"], HashValue::Int32(5));
+        assert_eq!(merged.paths_with(true).len(), 8);
+
+        // merging into an empty hash copies
+        let mut empty = Hash::new();
+        empty.merge(&config, MergePolicy::ReplaceAttributes);
+        assert_eq!(empty, config);
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut old = Hash::new();
+        assert!(old.insert("motor.position", HashValue::Float64(1.0)));
+        assert!(old.insert("motor.velocity", HashValue::Float64(2.0)));
+        assert!(old.insert("state", HashValue::String("ON".into())));
+        assert!(old.insert("rows[0].x", HashValue::Int32(1)));
+        old.set_attribute("motor.position", "unit", HashValue::String("mm".into()));
+        old.set_attribute("motor.position", "alarm", HashValue::Bool(false));
+        assert!(Hash::diff(&old, &old).is_empty());
+
+        let mut new = old.clone();
+        new.remove("state");
+        assert!(new.insert("motor.velocity", HashValue::Float32(2.0)));
+        assert!(new.insert("motor.extra.speed", HashValue::UInt8(3)));
+        assert!(new.insert("rows[0].x", HashValue::Int32(4)));
+        new.remove_attribute("motor.position", "unit");
+        new.set_attribute("motor.position", "alarm", HashValue::Bool(true));
+        new.set_attribute("motor", "tag", HashValue::Int32(1));
+
+        let diff = Hash::diff(&old, &new);
+        assert_eq!(
+            diff.added,
+            vec![("motor.extra".to_string(), new["motor.extra"].clone())]
+        );
+        assert_eq!(
+            diff.removed,
+            vec![("state".to_string(), HashValue::String("ON".into()))]
+        );
+        assert_eq!(
+            diff.changed,
+            vec![
+                ValueChange {
+                    path: "motor.velocity".into(),
+                    old: HashValue::Float64(2.0),
+                    new: HashValue::Float32(2.0),
+                },
+                ValueChange {
+                    path: "rows".into(),
+                    old: old["rows"].clone(),
+                    new: new["rows"].clone(),
+                },
+            ]
+        );
+        assert_eq!(
+            diff.attributes,
+            vec![
+                AttributeChange {
+                    path: "motor".into(),
+                    key: "tag".into(),
+                    old: None,
+                    new: Some(HashValue::Int32(1)),
+                },
+                AttributeChange {
+                    path: "motor.position".into(),
+                    key: "unit".into(),
+                    old: Some(HashValue::String("mm".into())),
+                    new: None,
+                },
+                AttributeChange {
+                    path: "motor.position".into(),
+                    key: "alarm".into(),
+                    old: Some(HashValue::Bool(false)),
+                    new: Some(HashValue::Bool(true)),
+                },
+            ]
+        );
+
+        let reconfigure = diff.to_hash().unwrap();
+        assert_eq!(
+            reconfigure.paths(),
+            vec!["motor.extra.speed", "motor.velocity", "rows"]
+        );
+        let mut applied = old.clone();
+        applied.merge(&reconfigure, MergePolicy::MergeAttributes);
+        assert_eq!(Hash::diff(&applied, &new).changed, vec![]);
+    }
+
+    #[test]
+    fn test_equality_variants() {
+        let mut read_buf = BufReader::new(File::open("./file.bin").unwrap());
+        let hash = read_hash(&mut read_buf).unwrap();
+        assert!(hash.fully_equals(&hash.clone()));
+
+        // same content in a different order
+        let mut reordered = Hash::new();
+        let nodes: Vec<_> = hash.iter().collect();
+        for (key, value, attrs) in nodes.into_iter().rev() {
+            assert!(reordered.insert_attrs(key, value.clone(), attrs.clone()));
+        }
+        assert!(reordered.equals_ignoring_order(&hash));
+        assert!(reordered == hash);
+        assert!(!reordered.fully_equals(&hash));
+        assert!(!reordered.equals_ignoring_attributes(&hash));
+
+        // order of nested nodes
+        let mut a = Hash::new();
+        assert!(a.insert("node.x", HashValue::Int32(1)));
+        assert!(a.insert("node.y", HashValue::Int32(2)));
+        let mut b = Hash::new();
+        assert!(b.insert("node.y", HashValue::Int32(2)));
+        assert!(b.insert("node.x", HashValue::Int32(1)));
+        assert!(a.equals_ignoring_order(&b));
+        assert!(!a.fully_equals(&b));
+        assert!(b.insert("node.x", HashValue::Int64(1)));
+        assert!(!a.equals_ignoring_order(&b));
+
+        // attributes
+        let mut b = a.clone();
+        b.set_attribute("node.x", "unit", HashValue::Int32(1));
+        assert!(a.equals_ignoring_attributes(&b));
+        assert!(!a.fully_equals(&b));
+        assert!(a != b);
+        a.set_attribute("node.x", "scale", HashValue::Int32(2));
+        a.set_attribute("node.x", "unit", HashValue::Int32(1));
+        b.set_attribute("node.x", "scale", HashValue::Int32(2));
+        assert!(a == b);
+        assert!(!a.fully_equals(&b));
+        let (x, y) = (
+            a.get_attributes("node.x").unwrap(),
+            b.get_attributes("node.x").unwrap(),
+        );
+        assert!(x.equals_ignoring_order(y));
+        assert!(!x.fully_equals(y));
+        assert!(x.fully_equals(&x.clone()));
+
+        // hashes held by attributes are compared in order too
+        let mut x = Attributes::new();
+        x.insert(
+            "h",
+            HashValue::Hash(crate::hash! { "a" => 1i32, "b" => 2i32 }),
+        );
+        let mut y = Attributes::new();
+        y.insert(
+            "h",
+            HashValue::Hash(crate::hash! { "b" => 2i32, "a" => 1i32 }),
+        );
+        assert!(x.equals_ignoring_order(&y));
+        assert!(!x.fully_equals(&y));
+    }
+
+    #[test]
+    fn test_typed_access() {
+        let mut hash = Hash::new();
+        assert!(hash.set("motor.position", 1.5));
+        assert!(hash.set("state", "ON"));
+        assert!(hash.set("flags", vec![true, false]));
+        assert!(hash.set("names", vec!["a", "b"]));
+        assert!(hash.set("bytes", vec![1u8, 2]));
+        assert!(hash.set("c", Complex::new(1.0f32, 2.0)));
+        assert!(hash.set("rows", vec![Hash::new()]));
+        assert!(hash.set("schema", Schema::new("Motor".into(), Hash::new())));
+        assert_eq!(hash["motor.position"], HashValue::Float64(1.5));
+        assert_eq!(
+            hash["names"],
+            HashValue::VectorString(vec!["a".into(), "b".into()])
+        );
+
+        assert_eq!(hash.get_as::<f64>("motor.position"), Ok(1.5));
+        assert_eq!(hash.get_as::<&str>("state"), Ok("ON"));
+        assert_eq!(hash.get_as::<String>("state"), Ok("ON".to_string()));
+        assert_eq!(hash.get_as::<&[bool]>("flags"), Ok(&[true, false][..]));
+        assert_eq!(hash.get_as::<Vec<u8>>("bytes"), Ok(vec![1, 2]));
+        assert_eq!(hash.get_as::<Complex<f32>>("c"), Ok(Complex::new(1.0, 2.0)));
+        assert_eq!(hash.get_as::<&[Hash]>("rows").unwrap().len(), 1);
+        assert_eq!(hash.get_as::<&Hash>("motor").unwrap().len(), 1);
+        assert_eq!(hash.get_as::<&Schema>("schema").unwrap().class_id, "Motor");
+        assert_eq!(
+            hash.get_as::<f32>("motor.position"),
+            Err(TypeError::Mismatch {
+                expected: "FLOAT",
+                found: "DOUBLE"
+            })
+        );
+        assert_eq!(
+            hash.get_as::<Vec<i32>>("state").unwrap_err().to_string(),
+            "expected VECTOR_INT32, found STRING"
+        );
+        assert_eq!(
+            hash.get_as::<bool>("missing"),
+            Err(TypeError::MissingPath {
+                path: "missing".into()
+            })
+        );
+        assert_eq!(
+            <&[u8]>::try_from(&HashValue::ByteArray(vec![3])),
+            Ok(&[3u8][..])
+        );
+        assert_eq!(i8::try_from(&HashValue::from(-1i8)), Ok(-1));
+
+        // set keeps the attributes
+        hash.set_attribute("state", "unit", HashValue::Int32(1));
+        assert!(hash.set("state", "OFF"));
+        assert_eq!(hash.get_as::<&str>("state"), Ok("OFF"));
+        assert!(hash.get_attribute("state", "unit").is_some());
+    }
+
+    #[test]
+    fn test_coercion() {
+        // widening and narrowing
+        assert_eq!(HashValue::Int8(-1).coerce::<i64>(), Ok(-1));
+        assert_eq!(HashValue::UInt64(200).coerce::<u8>(), Ok(200));
+        assert_eq!(HashValue::Float64(3.0).coerce::<i16>(), Ok(3));
+        assert_eq!(HashValue::Float64(0.5).coerce::<f32>(), Ok(0.5));
+        assert_eq!(HashValue::Int32(7).coerce::<f64>(), Ok(7.0));
+        assert_eq!(HashValue::Bool(true).coerce::<u32>(), Ok(1));
+        assert_eq!(HashValue::UInt16(2).coerce::<bool>(), Ok(true));
+        assert_eq!(
+            HashValue::Int16(300).coerce::<u8>(),
+            Err(TypeError::OutOfRange {
+                value: "300".into(),
+                expected: "UINT8"
+            })
+        );
+        assert_eq!(
+            HashValue::Int8(-1).coerce::<u64>().unwrap_err().to_string(),
+            "-1 is out of range for UINT64"
+        );
+        assert_eq!(
+            HashValue::Float64(1.5).coerce::<i32>(),
+            Err(TypeError::PrecisionLoss {
+                value: "1.5".into(),
+                expected: "INT32"
+            })
+        );
+        assert!(matches!(
+            HashValue::Float64(0.1).coerce::<f32>(),
+            Err(TypeError::PrecisionLoss { .. })
+        ));
+        assert!(matches!(
+            HashValue::Float64(1e300).coerce::<f32>(),
+            Err(TypeError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            HashValue::Float64(f64::INFINITY).coerce::<i64>(),
+            Err(TypeError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            HashValue::UInt64(u64::MAX).coerce::<f64>(),
+            Err(TypeError::PrecisionLoss { .. })
+        ));
+        assert!(HashValue::Float64(f64::NAN)
+            .coerce::<f32>()
+            .unwrap()
+            .is_nan());
+
+        // strings
+        assert_eq!(HashValue::String(" 42 ".into()).coerce::<u16>(), Ok(42));
+        assert_eq!(HashValue::String("1e3".into()).coerce::<i32>(), Ok(1000));
+        assert_eq!(HashValue::String("true".into()).coerce::<bool>(), Ok(true));
+        assert_eq!(
+            HashValue::String("on".into()).coerce::<f64>(),
+            Err(TypeError::Parse {
+                value: "on".into(),
+                expected: "DOUBLE"
+            })
+        );
+        assert_eq!(
+            HashValue::Float64(200.0).coerce::<String>(),
+            Ok("200".into())
+        );
+        assert_eq!(HashValue::Int8(-1).coerce::<String>(), Ok("-1".into()));
+        // FLOAT is parsed and formatted as such, not through DOUBLE
+        assert_eq!(HashValue::String("0.1".into()).coerce::<f32>(), Ok(0.1));
+        assert_eq!(
+            HashValue::VectorString(vec!["0.1".into(), "-2.3e-5".into()]).coerce::<Vec<f32>>(),
+            Ok(vec![0.1, -2.3e-5])
+        );
+        assert_eq!(HashValue::Float32(0.1).coerce::<String>(), Ok("0.1".into()));
+        assert_eq!(
+            HashValue::Float32(0.1).coerce::<f64>(),
+            Ok(f64::from(0.1f32))
+        );
+        assert_eq!(
+            HashValue::Float32(1.5).coerce::<u8>(),
+            Err(TypeError::PrecisionLoss {
+                value: "1.5".into(),
+                expected: "UINT8"
+            })
+        );
+        let floats = HashValue::VectorFloat32(vec![0.1, 2.5]);
+        assert_eq!(floats.coerce::<String>(), Ok("0.1,2.5".into()));
+        assert_eq!(
+            floats.coerce::<Vec<String>>(),
+            Ok(vec!["0.1".to_string(), "2.5".to_string()])
+        );
+        assert_eq!(HashValue::Char('x').coerce::<String>(), Ok("x".into()));
+
+        // vectors
+        let value = HashValue::VectorInt32(vec![1, 2, 300]);
+        assert_eq!(value.coerce::<Vec<f64>>(), Ok(vec![1.0, 2.0, 300.0]));
+        assert_eq!(
+            value.coerce::<Vec<String>>(),
+            Ok(vec!["1".into(), "2".into(), "300".into()])
+        );
+        assert_eq!(value.coerce::<String>(), Ok("1,2,300".into()));
+        assert!(matches!(
+            value.coerce::<Vec<i8>>(),
+            Err(TypeError::OutOfRange { .. })
+        ));
+        assert_eq!(
+            HashValue::String("1, 2,3".into()).coerce::<Vec<u8>>(),
+            Ok(vec![1, 2, 3])
+        );
+        assert_eq!(
+            HashValue::String(String::new()).coerce::<Vec<u8>>(),
+            Ok(vec![])
+        );
+        assert_eq!(
+            HashValue::VectorString(vec!["1.5".into()]).coerce::<Vec<f32>>(),
+            Ok(vec![1.5])
+        );
+        assert_eq!(
+            HashValue::VectorFloat32(vec![1.0, 2.0]).coerce::<&[f32]>(),
+            Ok(&[1.0f32, 2.0][..])
+        );
+
+        let mut read_buf = BufReader::new(File::open("./file.bin").unwrap());
+        let hash = read_hash(&mut read_buf).unwrap();
+        assert_eq!(hash.get_coerced::<f64>("i8"), Ok(-1.0));
+        assert_eq!(hash.get_coerced::<String>("node.f64"), Ok("200".into()));
+        assert_eq!(hash.get_coerced::<Vec<f64>>("vf32"), Ok(vec![12.0]));
+        assert!(matches!(
+            hash.get_coerced::<f64>("node"),
+            Err(TypeError::Mismatch {
+                expected: "DOUBLE",
+                found: "HASH"
+            })
+        ));
+    }
+
+    #[test]
+    fn test_hash_macro() {
+        assert!(crate::hash! {}.is_empty());
+
+        let hash = crate::hash! {
+            "a" => 1i32,
+            "b" => { "c" => "text" @ { "unit" => "m", "scale" => 2u8 } } @ { "tag" => true },
+            "v" => vec![1.0f64, 2.0],
+            "empty" => {},
+            "rows" => [{ "x" => 1u8 }, { "x" => 2u8, "y" => Complex::new(1.0f32, 0.0) }],
+            "d.e" => -1i8 + 2,
+            "n" => HashValue::None,
+        };
+
+        let mut expected = Hash::new();
+        assert!(expected.insert("a", HashValue::Int32(1)));
+        assert!(expected.insert("b.c", HashValue::String("text".into())));
+        expected.set_attribute("b.c", "unit", HashValue::String("m".into()));
+        expected.set_attribute("b.c", "scale", HashValue::UInt8(2));
+        expected.set_attribute("b", "tag", HashValue::Bool(true));
+        assert!(expected.insert("v", HashValue::VectorFloat64(vec![1.0, 2.0])));
+        assert!(expected.insert("empty", HashValue::Hash(Hash::new())));
+        let mut row0 = Hash::new();
+        assert!(row0.insert("x", HashValue::UInt8(1)));
+        let mut row1 = Hash::new();
+        assert!(row1.insert("x", HashValue::UInt8(2)));
+        assert!(row1.insert("y", HashValue::ComplexFloat32(Complex::new(1.0, 0.0))));
+        assert!(expected.insert("rows", HashValue::VectorHash(vec![row0, row1])));
+        assert!(expected.insert("d.e", HashValue::Int8(1)));
+        assert!(expected.insert("n", HashValue::None));
+        assert!(hash.fully_equals(&expected));
+    }
+
+    #[test]
+    #[should_panic(expected = "hash!: invalid path rows[1].x")]
+    fn test_hash_macro_invalid_path() {
+        let _ = crate::hash! { "rows[1].x" => 1i32, "a" => 2i32 };
+    }
+
+    #[test]
+    fn test_json() {
+        let mut read_buf = BufReader::new(File::open("./file.bin").unwrap());
+        let hash = read_hash(&mut read_buf).unwrap();
+        let json = hash.to_json();
+        assert!(Hash::from_json(&json).unwrap().fully_equals(&hash));
+
+        let mut hash = crate::hash! {
+            "u64" => u64::MAX @ { "unit" => "m", "i64" => i64::MIN },
+            "f32" => 0.1f32,
+            "inf" => vec![f64::INFINITY, f64::NEG_INFINITY, 1e300],
+            "c" => Complex::new(1.5f64, -2.0),
+            "text" => "a \"quoted\"\n\u{1}é😀",
+            "none" => HashValue::None,
+            "nones" => HashValue::VectorNone(2),
+            "bytes" => HashValue::ByteArray(vec![0, 1, 2, 253, 254]),
+            "rows" => [{ "x" => 'x' }, {}],
+        };
+        assert!(hash.insert(
+            "schema",
+            HashValue::Schema(Schema::new("Motor".into(), crate::hash! { "a" => 1i16 })),
+        ));
+        let json = hash.to_json();
+        assert!(json.starts_with(
+            r#"{"u64":{"type":"UINT64","value":"18446744073709551615","attrs":{"unit":{"type":"STRING","value":"m"},"#
+        ));
+        assert!(json.contains(r#""bytes":{"type":"BYTE_ARRAY","value":"AAEC/f4="}"#));
+        assert!(Hash::from_json(&json).unwrap().fully_equals(&hash));
+
+        let nan = Hash::from_json(r#"{"x": {"type": "DOUBLE", "value": "NaN"}}"#).unwrap();
+        assert!(nan["x"].as_f64().unwrap().is_nan());
+
+        assert_eq!(
+            crate::hash! {
+                "a" => 1u64 @ { "unit" => "m" },
+                "b" => { "c" => vec![f32::NAN, 2.5] },
+                "d" => [{ "e" => "f" }],
+            }
+            .to_json_with(JsonMode::Plain),
+            r#"{"a":1,"b":{"c":[null,2.5]},"d":[{"e":"f"}]}"#
+        );
+
+        assert_eq!(
+            Hash::from_json(r#"{"a": {"type": "UINT8", "value": 300}}"#),
+            Err(JsonError::InvalidValue {
+                type_: "UINT8".into(),
+                path: "a".into()
+            })
+        );
+        assert_eq!(
+            Hash::from_json(
+                r#"{"a": {"type": "HASH", "value": {"b": {"type": "INT128", "value": 1}}}}"#
+            ),
+            Err(JsonError::UnknownType {
+                type_: "INT128".into(),
+                path: "a.b".into()
+            })
+        );
+        assert_eq!(
+            Hash::from_json(r#"{"a": 1}"#),
+            Err(JsonError::InvalidValue {
+                type_: "typed node".into(),
+                path: "a".into()
+            })
+        );
+        assert_eq!(
+            Hash::from_json(r#"{"a": {"type": "INT8", "value": 1},}"#),
+            Err(JsonError::Syntax {
+                offset: 35,
+                expected: "a key"
+            })
+        );
+        assert!(matches!(
+            Hash::from_json(&"[".repeat(1000)),
+            Err(JsonError::TooDeep { .. })
+        ));
+
+        let emoji = Hash::from_json(r#"{"s": {"type": "STRING", "value": "\ud83d\ude00"}}"#);
+        assert_eq!(
+            emoji.unwrap()["s"],
+            HashValue::String("\u{1f600}".to_string())
+        );
+        for string in [r#""\ud83dA""#, r#""\ud83d""#, r#""\ude00""#, r#""\u+041""#] {
+            let json = format!(r#"{{"s": {{"type": "STRING", "value": {string}}}}}"#);
+            assert!(matches!(
+                Hash::from_json(&json),
+                Err(JsonError::Syntax { .. })
+            ));
+        }
+
+        let number = Hash::from_json(r#"{"x": {"type": "DOUBLE", "value": -0.5e-3}}"#);
+        assert_eq!(number.unwrap()["x"].as_f64(), Some(-0.0005));
+        for number in ["01", "1.", "-", "1e", ".5", "+1", "1.e3", "-01"] {
+            let json = format!(r#"{{"x": {{"type": "DOUBLE", "value": {number}}}}}"#);
+            assert!(
+                matches!(Hash::from_json(&json), Err(JsonError::Syntax { .. })),
+                "{number}"
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use crate::hash_deserializer::{from_hash, from_value};
+        use crate::hash_serializer::{rows, to_hash, to_value, typed, SerdeError};
+        use serde::{Deserialize, Serialize};
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Mode {
+            Off,
+            Speed(u16),
+            Range { min: f32, max: f32 },
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Axis {
+            name: String,
+            offset: i8,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Motor {
+            position: f64,
+            #[serde(rename = "position@unit")]
+            unit: String,
+            steps: u32,
+            enabled: bool,
+            tags: Vec<String>,
+            gains: Vec<i64>,
+            axes: Vec<Axis>,
+            home: Axis,
+            limit: Option<u8>,
+            modes: Vec<Mode>,
+            mode: Mode,
+        }
+
+        let motor = Motor {
+            position: 1.5,
+            unit: "mm".into(),
+            steps: 200,
+            enabled: true,
+            tags: vec!["a".into(), "b".into()],
+            gains: vec![1, -2],
+            axes: vec![
+                Axis {
+                    name: "x".into(),
+                    offset: -1,
+                },
+                Axis {
+                    name: "y".into(),
+                    offset: 1,
+                },
+            ],
+            home: Axis {
+                name: "z".into(),
+                offset: 0,
+            },
+            limit: None,
+            modes: vec![Mode::Off, Mode::Off],
+            mode: Mode::Range { min: 0.0, max: 1.0 },
+        };
+        let hash = to_hash(&motor).unwrap();
+        assert_eq!(
+            hash.keys(),
+            vec![
+                "position", "steps", "enabled", "tags", "gains", "axes", "home", "limit", "modes",
+                "mode"
+            ]
+        );
+        assert_eq!(hash["position"], HashValue::Float64(1.5));
+        assert_eq!(
+            hash.get_attribute("position", "unit"),
+            Some(&HashValue::String("mm".into()))
+        );
+        assert_eq!(hash["steps"], HashValue::UInt32(200));
+        assert_eq!(hash["gains"], HashValue::VectorInt64(vec![1, -2]));
+        assert_eq!(
+            hash["tags"],
+            HashValue::VectorString(vec!["a".into(), "b".into()])
+        );
+        assert_eq!(hash["axes[1].offset"], HashValue::Int8(1));
+        assert_eq!(hash["home.name"], HashValue::String("z".into()));
+        assert_eq!(hash["limit"], HashValue::None);
+        assert_eq!(
+            hash["modes"],
+            HashValue::VectorString(vec!["Off".into(), "Off".into()])
+        );
+        assert_eq!(hash["mode.Range.max"], HashValue::Float32(1.0));
+        assert_eq!(from_hash::<Motor>(&hash).unwrap(), motor);
+
+        // survives the binary format
+        let mut buf = Vec::new();
+        write_hash(&mut buf, &hash).unwrap();
+        let read = read_hash(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(from_hash::<Motor>(&read).unwrap(), motor);
+
+        // integers convert to any width holding the value
+        let mut hash = to_hash(&motor).unwrap();
+        assert!(hash.set("steps", 7u8));
+        assert!(hash.set("limit", 3i64));
+        assert!(hash.set("mode", to_value(&Mode::Speed(3)).unwrap()));
+        let read = from_hash::<Motor>(&hash).unwrap();
+        assert_eq!(
+            (read.steps, read.limit, read.mode),
+            (7, Some(3), Mode::Speed(3))
+        );
+        assert!(hash.set("steps", -1i32));
+        assert!(from_hash::<Motor>(&hash).is_err());
+
+        let map: BTreeMap<String, Vec<u16>> = [("a".to_string(), vec![1u16])].into();
+        let value = to_value(&map).unwrap();
+        assert_eq!(
+            value.as_hash().unwrap()["a"],
+            HashValue::VectorUInt16(vec![1])
+        );
+        assert_eq!(
+            from_value::<BTreeMap<String, Vec<u16>>>(&value).unwrap(),
+            map
+        );
+        // empty vectors need their type
+        assert_eq!(to_value(&Vec::<i32>::new()), Err(SerdeError::EmptySequence));
+        #[derive(Serialize)]
+        struct Empty {
+            #[serde(serialize_with = "typed")]
+            values: Vec<f64>,
+            #[serde(serialize_with = "typed")]
+            counts: Vec<u16>,
+            #[serde(serialize_with = "typed")]
+            names: Vec<String>,
+            #[serde(serialize_with = "rows")]
+            axes: Vec<Axis>,
+        }
+        let empty = Empty {
+            values: vec![],
+            counts: vec![],
+            names: vec!["a".into()],
+            axes: vec![],
+        };
+        let hash = to_hash(&empty).unwrap();
+        assert!(hash.fully_equals(&crate::hash! {
+            "values" => HashValue::VectorFloat64(vec![]),
+            "counts" => HashValue::VectorUInt16(vec![]),
+            "names" => vec!["a".to_string()],
+            "axes" => HashValue::VectorHash(vec![]),
+        }));
+        #[derive(Serialize)]
+        struct Untyped {
+            values: Vec<f64>,
+        }
+        assert_eq!(
+            to_hash(&Untyped { values: vec![] }),
+            Err(SerdeError::EmptySequence)
+        );
+        assert_eq!(
+            from_value::<Vec<i32>>(&HashValue::VectorNone(0)),
+            Ok(vec![])
+        );
+
+        assert_eq!(to_hash(&1u8), Err(SerdeError::NotAHash { found: "UINT8" }));
+        assert_eq!(
+            to_value(&(1u8, "a")),
+            Err(SerdeError::MixedSequence {
+                first: "UINT8",
+                found: "STRING"
+            })
+        );
+        assert_eq!(
+            to_value(&vec![vec![1u8]]),
+            Err(SerdeError::UnsupportedElement {
+                type_: "VECTOR_UINT8"
+            })
+        );
+        // keys are literal, `.` and `[` don't make paths
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Dotted {
+            #[serde(rename = "a.b")]
+            value: u8,
+            #[serde(rename = "a.b@unit")]
+            unit: String,
+            #[serde(rename = "c[0]@tag")]
+            tag: bool,
+            #[serde(rename = "c[0]")]
+            row: i16,
+        }
+        let dotted = Dotted {
+            value: 1,
+            unit: "m".into(),
+            tag: true,
+            row: -1,
+        };
+        let hash = to_hash(&dotted).unwrap();
+        assert_eq!(hash.keys(), vec!["a.b", "c[0]"]);
+        let node = hash.get_key("a.b").unwrap();
+        assert_eq!(node.attrs.get("unit"), Some(&HashValue::String("m".into())));
+        assert_eq!(
+            hash.get_key("c[0]").unwrap().attrs.get("tag"),
+            Some(&HashValue::Bool(true))
+        );
+        assert_eq!(from_hash::<Dotted>(&hash).unwrap(), dotted);
+        let map: BTreeMap<&str, &str> = [("a.b", "x"), ("a.b@unit", "m")].into();
+        let hash = to_hash(&map).unwrap();
+        assert_eq!(hash.len(), 1);
+        assert_eq!(
+            hash.get_key("a.b").unwrap().attrs.get("unit"),
+            Some(&HashValue::String("m".into()))
+        );
+
+        let map: BTreeMap<u8, u8> = [(1, 1)].into();
+        assert_eq!(to_value(&map), Err(SerdeError::KeyMustBeString));
+        let map: BTreeMap<&str, u8> = [("x@unit", 1)].into();
+        assert_eq!(
+            to_value(&map),
+            Err(SerdeError::MissingNode {
+                node: "x".into(),
+                attr: "unit".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_derive_karabo_hash() {
+        use karabo_derive::KaraboHash;
+
+        #[derive(Clone, Debug, PartialEq, KaraboHash)]
+        struct Axis {
+            #[karabo(attr(unit = "mm", precision = 3))]
+            offset: f64,
+        }
+
+        #[derive(Clone, Debug, PartialEq, KaraboHash)]
+        struct Motor {
+            #[karabo(
+                key = "actualPosition",
+                displayed_name = "Position",
+                access = "read_only",
+                coerce
+            )]
+            position: f32,
+            #[karabo(default = 10u16, description = "Steps per turn", access = "init")]
+            steps: u16,
+            #[karabo(default = "ON")]
+            state: String,
+            gains: Vec<i32>,
+            axis: Axis,
+        }
+
+        let motor = Motor {
+            position: 1.5,
+            steps: 200,
+            state: "OFF".into(),
+            gains: vec![1, 2],
+            axis: Axis { offset: 0.5 },
+        };
+        let hash: Hash = motor.clone().into();
+        assert_eq!(
+            hash.keys(),
+            vec!["actualPosition", "steps", "state", "gains", "axis"]
+        );
+        assert_eq!(hash["actualPosition"], HashValue::Float32(1.5));
+        assert_eq!(hash["axis.offset"], HashValue::Float64(0.5));
+        assert_eq!(
+            hash.get_attribute("axis.offset", "unit"),
+            Some(&HashValue::String("mm".into()))
+        );
+        assert_eq!(
+            hash.get_attribute("axis.offset", "precision"),
+            Some(&HashValue::Int32(3))
+        );
+        assert_eq!(Motor::try_from(&hash), Ok(motor.clone()));
+
+        // fields match exactly unless they are read with `coerce`
+        let mut wrong = hash.clone();
+        assert!(wrong.set("steps", "3"));
+        assert_eq!(
+            Motor::try_from(&wrong),
+            Err(TypeError::Mismatch {
+                expected: "UINT16",
+                found: "STRING"
+            })
+        );
+        assert!(wrong.set("steps", 3.0f64));
+        assert!(Motor::try_from(&wrong).is_err());
+
+        // defaults
+        let mut partial = hash.clone();
+        partial.remove("steps");
+        partial.remove("state");
+        assert!(partial.set("actualPosition", 2i32));
+        let read = Motor::try_from(&partial).unwrap();
+        assert_eq!(
+            (read.position, read.steps, read.state.as_str()),
+            (2.0, 10, "ON")
+        );
+        partial.remove("gains");
+        assert_eq!(
+            Motor::try_from(&partial),
+            Err(TypeError::MissingPath {
+                path: "gains".into()
+            })
+        );
+        assert!(partial.set("gains", vec![1i32]));
+        assert!(partial.set("axis", 1u8));
+        assert_eq!(
+            Motor::try_from(&partial),
+            Err(TypeError::Mismatch {
+                expected: "HASH",
+                found: "UINT8"
+            })
+        );
+
+        let schema = Motor::karabo_schema();
+        assert_eq!(schema.class_id, "Motor");
+        let schema = schema.hash;
+        let position = schema.get_attributes("actualPosition").unwrap();
+        assert_eq!(
+            position.get("valueType"),
+            Some(&HashValue::String("FLOAT".into()))
+        );
+        assert_eq!(
+            position.get("displayedName"),
+            Some(&HashValue::String("Position".into()))
+        );
+        assert_eq!(
+            position.get("accessMode"),
+            Some(&HashValue::Int32(crate::schema::READ))
+        );
+        assert_eq!(position.get("nodeType"), Some(&HashValue::Int32(0)));
+        let steps = schema.get_attributes("steps").unwrap();
+        assert_eq!(steps.get("defaultValue"), Some(&HashValue::UInt16(10)));
+        assert_eq!(
+            steps.get("accessMode"),
+            Some(&HashValue::Int32(crate::schema::INIT))
+        );
+        assert_eq!(
+            steps.get("description"),
+            Some(&HashValue::String("Steps per turn".into()))
+        );
+        assert_eq!(
+            schema.get_attribute("state", "defaultValue"),
+            Some(&HashValue::String("ON".into()))
+        );
+        assert_eq!(
+            schema.get_attribute("gains", "valueType"),
+            Some(&HashValue::String("VECTOR_INT32".into()))
+        );
+        assert_eq!(
+            schema.get_attribute("axis", "nodeType"),
+            Some(&HashValue::Int32(1))
+        );
+        assert_eq!(
+            schema.get_attribute("axis.offset", "valueType"),
+            Some(&HashValue::String("DOUBLE".into()))
+        );
+        assert_eq!(
+            schema.get_attribute("axis.offset", "unit"),
+            Some(&HashValue::String("mm".into()))
+        );
+    }
 }
@@ -1,8 +1,86 @@
 use std::fmt;
 
+use crate::convert::Coerce;
 use crate::hash::Hash;
 use crate::schema::Schema;
 
+// Mirrors std::complex, serialized as the real part followed by the imaginary part
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct Complex<T> {
+    pub re: T,
+    pub im: T,
+}
+
+impl<T> Complex<T> {
+    pub fn new(re: T, im: T) -> Self {
+        Complex { re, im }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Complex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({},{})", self.re, self.im)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypeError {
+    // the value is not of the requested type
+    Mismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    MissingPath {
+        path: String,
+    },
+    // the value does not fit in the requested numeric type
+    OutOfRange {
+        value: String,
+        expected: &'static str,
+    },
+    // e.g. a fractional value as an integer, or a large integer as a float
+    PrecisionLoss {
+        value: String,
+        expected: &'static str,
+    },
+    Parse {
+        value: String,
+        expected: &'static str,
+    },
+}
+
+impl TypeError {
+    pub(crate) fn mismatch(expected: &'static str, value: &HashValue) -> Self {
+        TypeError::Mismatch {
+            expected,
+            found: get_typename(value),
+        }
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeError::Mismatch { expected, found } => {
+                write!(f, "expected {expected}, found {found}")
+            }
+            TypeError::MissingPath { path } => write!(f, "no value at '{path}'"),
+            TypeError::OutOfRange { value, expected } => {
+                write!(f, "{value} is out of range for {expected}")
+            }
+            TypeError::PrecisionLoss { value, expected } => {
+                write!(f, "{value} can't be represented exactly as {expected}")
+            }
+            TypeError::Parse { value, expected } => {
+                write!(f, "can't parse '{value}' as {expected}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TypeError {}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum HashValue {
     Bool(bool),
@@ -29,14 +107,29 @@ pub enum HashValue {
     VectorFloat32(Vec<f32>),
     Float64(f64),
     VectorFloat64(Vec<f64>),
+    ComplexFloat32(Complex<f32>),
+    VectorComplexFloat32(Vec<Complex<f32>>),
+    ComplexFloat64(Complex<f64>),
+    VectorComplexFloat64(Vec<Complex<f64>>),
     String(String),
     VectorString(Vec<String>),
     Hash(Hash),
     VectorHash(Vec<Hash>),
     Schema(Schema),
+    None,
+    VectorNone(usize),
+    ByteArray(Vec<u8>),
 }
 
 impl HashValue {
+    // Like Karabo's `getAs`, the value converted to `T` whatever its type:
+    // numbers are widened and narrowed, vectors element by element, and
+    // strings are parsed or formatted. A conversion that would overflow or
+    // lose precision fails. See `convert::Coerce` for the details.
+    pub fn coerce<'a, T: Coerce<'a>>(&'a self) -> Result<T, TypeError> {
+        T::coerce(self)
+    }
+
     pub fn as_i8(&self) -> Option<i8> {
         match *self {
             HashValue::Int8(value) => Some(value),
@@ -97,6 +190,41 @@ impl HashValue {
             _ => None,
         }
     }
+    pub fn as_complex_f32(&self) -> Option<Complex<f32>> {
+        match *self {
+            HashValue::ComplexFloat32(value) => Some(value),
+            _ => None,
+        }
+    }
+    pub fn as_complex_f64(&self) -> Option<Complex<f64>> {
+        match *self {
+            HashValue::ComplexFloat64(value) => Some(value),
+            _ => None,
+        }
+    }
+    pub fn as_vector_complex_f32(&self) -> Option<&[Complex<f32>]> {
+        match self {
+            HashValue::VectorComplexFloat32(value) => Some(value),
+            _ => None,
+        }
+    }
+    pub fn as_vector_complex_f64(&self) -> Option<&[Complex<f64>]> {
+        match self {
+            HashValue::VectorComplexFloat64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(self, HashValue::None)
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            HashValue::ByteArray(value) => Some(value),
+            _ => None,
+        }
+    }
 
     pub fn as_hash(&self) -> Option<&Hash> {
         match self {
@@ -140,6 +268,16 @@ impl fmt::Display for HashValue {
             HashValue::VectorFloat32(x) => write!(f, "VECTOR_FLOAT {x:?}"),
             HashValue::Float64(x) => write!(f, "DOUBLE {x}"),
             HashValue::VectorFloat64(x) => write!(f, "VECTOR_DOUBLE {x:?}"),
+            HashValue::ComplexFloat32(x) => write!(f, "COMPLEX_FLOAT {x}"),
+            HashValue::VectorComplexFloat32(x) => {
+                let ret: Vec<String> = x.iter().map(|c| format!("{}", c)).collect();
+                write!(f, "VECTOR_COMPLEX_FLOAT [{}]", ret.join(", "))
+            }
+            HashValue::ComplexFloat64(x) => write!(f, "COMPLEX_DOUBLE {x}"),
+            HashValue::VectorComplexFloat64(x) => {
+                let ret: Vec<String> = x.iter().map(|c| format!("{}", c)).collect();
+                write!(f, "VECTOR_COMPLEX_DOUBLE [{}]", ret.join(", "))
+            }
             HashValue::String(x) => write!(f, "STRING '{x}'"),
             HashValue::VectorString(x) => write!(f, "VECTOR_STRING {x:?}"),
             HashValue::Hash(x) => write!(f, "HASH {x}"),
@@ -149,6 +287,9 @@ impl fmt::Display for HashValue {
                 write!(f, "VECTOR_HASH [{}]", ret)
             }
             HashValue::Schema(x) => write!(f, "SCHEMA ClassId {} {}", x.class_id, x.hash),
+            HashValue::None => write!(f, "NONE"),
+            HashValue::VectorNone(x) => write!(f, "VECTOR_NONE {x}"),
+            HashValue::ByteArray(x) => write!(f, "BYTE_ARRAY {} bytes", x.len()),
         }
     }
 }
@@ -179,10 +320,59 @@ pub fn get_hashtype(value: &HashValue) -> u32 {
         HashValue::VectorFloat32(_) => 21,
         HashValue::Float64(_) => 22,
         HashValue::VectorFloat64(_) => 23,
+        HashValue::ComplexFloat32(_) => 24,
+        HashValue::VectorComplexFloat32(_) => 25,
+        HashValue::ComplexFloat64(_) => 26,
+        HashValue::VectorComplexFloat64(_) => 27,
         HashValue::String(_) => 28,
         HashValue::VectorString(_) => 29,
         HashValue::Hash(_) => 30,
         HashValue::VectorHash(_) => 31,
         HashValue::Schema(_) => 32,
+        HashValue::None => 34,
+        HashValue::VectorNone(_) => 35,
+        HashValue::ByteArray(_) => 37,
+    }
+}
+
+// Karabo name of the type of `value`
+pub fn get_typename(value: &HashValue) -> &'static str {
+    match value {
+        HashValue::Bool(_) => "BOOL",
+        HashValue::VectorBool(_) => "VECTOR_BOOL",
+        HashValue::Char(_) => "CHAR",
+        HashValue::VectorChar(_) => "VECTOR_CHAR",
+        HashValue::Int8(_) => "INT8",
+        HashValue::VectorInt8(_) => "VECTOR_INT8",
+        HashValue::UInt8(_) => "UINT8",
+        HashValue::VectorUInt8(_) => "VECTOR_UINT8",
+        HashValue::Int16(_) => "INT16",
+        HashValue::VectorInt16(_) => "VECTOR_INT16",
+        HashValue::UInt16(_) => "UINT16",
+        HashValue::VectorUInt16(_) => "VECTOR_UINT16",
+        HashValue::Int32(_) => "INT32",
+        HashValue::VectorInt32(_) => "VECTOR_INT32",
+        HashValue::UInt32(_) => "UINT32",
+        HashValue::VectorUInt32(_) => "VECTOR_UINT32",
+        HashValue::Int64(_) => "INT64",
+        HashValue::VectorInt64(_) => "VECTOR_INT64",
+        HashValue::UInt64(_) => "UINT64",
+        HashValue::VectorUInt64(_) => "VECTOR_UINT64",
+        HashValue::Float32(_) => "FLOAT",
+        HashValue::VectorFloat32(_) => "VECTOR_FLOAT",
+        HashValue::Float64(_) => "DOUBLE",
+        HashValue::VectorFloat64(_) => "VECTOR_DOUBLE",
+        HashValue::ComplexFloat32(_) => "COMPLEX_FLOAT",
+        HashValue::VectorComplexFloat32(_) => "VECTOR_COMPLEX_FLOAT",
+        HashValue::ComplexFloat64(_) => "COMPLEX_DOUBLE",
+        HashValue::VectorComplexFloat64(_) => "VECTOR_COMPLEX_DOUBLE",
+        HashValue::String(_) => "STRING",
+        HashValue::VectorString(_) => "VECTOR_STRING",
+        HashValue::Hash(_) => "HASH",
+        HashValue::VectorHash(_) => "VECTOR_HASH",
+        HashValue::Schema(_) => "SCHEMA",
+        HashValue::None => "NONE",
+        HashValue::VectorNone(_) => "VECTOR_NONE",
+        HashValue::ByteArray(_) => "BYTE_ARRAY",
     }
 }
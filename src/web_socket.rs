@@ -1,5 +1,6 @@
-use crate::binary_readers::read_hash;
-use std::io::{Cursor, Seek, SeekFrom, Write};
+use crate::frame_decoder::FrameDecoder;
+use std::cell::RefCell;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{ErrorEvent, MessageEvent, WebSocket};
@@ -14,6 +15,23 @@ extern "C" {
     fn log(s: &str);
 }
 
+fn decode_frames(decoder: &RefCell<FrameDecoder>, data: &[u8]) {
+    let mut decoder = decoder.borrow_mut();
+    decoder.push(data);
+    loop {
+        match decoder.next_frame() {
+            Ok(Some(hash)) => console_log!("Hash keys: {:?}", hash.keys()),
+            Ok(None) => break,
+            Err(err) => {
+                console_log!("error decoding hash: {}", err);
+                if decoder.is_failed() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
 // Taken from https://rustwasm.github.io/wasm-bindgen/examples/websockets.html
 #[wasm_bindgen]
 pub fn start_websocket(uri: &str) -> Result<(), JsValue> {
@@ -22,44 +40,24 @@ pub fn start_websocket(uri: &str) -> Result<(), JsValue> {
     // For small binary messages, like CBOR, Arraybuffer is more efficient than Blob handling
     ws.set_binary_type(web_sys::BinaryType::Arraybuffer);
 
+    // messages are not guaranteed to contain exactly one frame
+    let decoder = Rc::new(RefCell::new(FrameDecoder::new()));
     let onmessage_callback = Closure::<dyn FnMut(_)>::new(move |e: MessageEvent| {
         // Handle difference Text/Binary,...
         if let Ok(abuf) = e.data().dyn_into::<js_sys::ArrayBuffer>() {
             console_log!("message event, received arraybuffer: {:?}", abuf);
             let array = js_sys::Uint8Array::new(&abuf);
-            let len = array.byte_length() as usize;
-            let _array = array.to_vec();
-            // here you can for example use Serde Deserialize decode the message
-            // for demo purposes we switch back to Blob-type and send off another binary message
-            let size_buf: [u8; 4] = _array[0..4].try_into().unwrap();
-            let karabo_len = u32::from_le_bytes(size_buf);
-            let mut c = Cursor::new(Vec::new());
-            console_log!("Hash of {} bytes was {}", karabo_len, len);
-            // Write into the "file" and seek to the beginning
-            c.write_all(&_array).unwrap();
-            c.seek(SeekFrom::Start(4)).unwrap();
-            let hash = read_hash(&mut c).unwrap();
-            console_log!("Hash keys: {:?}", hash.keys());
-            // here you can for example use the received image/png data
+            decode_frames(&decoder, &array.to_vec());
         } else if let Ok(blob) = e.data().dyn_into::<web_sys::Blob>() {
             console_log!("message event, received blob: {:?}", blob);
             // better alternative to juggling with FileReader is to use https://crates.io/crates/gloo-file
             let fr = web_sys::FileReader::new().unwrap();
             let fr_c = fr.clone();
+            let decoder = decoder.clone();
             // create onLoadEnd callback
             let onloadend_cb = Closure::<dyn FnMut(_)>::new(move |_e: web_sys::ProgressEvent| {
                 let array = js_sys::Uint8Array::new(&fr_c.result().unwrap());
-                let _array = array.to_vec();
-                let size_buf: [u8; 4] = _array[0..4].try_into().unwrap();
-                let karabo_len = u32::from_le_bytes(size_buf);
-                let mut c = Cursor::new(Vec::new());
-                console_log!("Hash of {}bytes", karabo_len);
-                // Write into the "file" and seek to the beginning
-                c.write_all(&_array).unwrap();
-                c.seek(SeekFrom::Start(4)).unwrap();
-                let hash = read_hash(&mut c).unwrap();
-                console_log!("Hash keys: {:?}", hash.keys());
-                // here you can for example use the received image/png data
+                decode_frames(&decoder, &array.to_vec());
             });
             fr.set_onloadend(Some(onloadend_cb.as_ref().unchecked_ref()));
             fr.read_as_array_buffer(&blob).expect("blob not readable");